@@ -1,6 +1,10 @@
 use proc_macro2::TokenStream;
 use quote::{ToTokens, quote};
 
+/// Environment variable holding the url of the database.
+pub const DB_URL_KEY: &str = "MODELLER_DATABASE_URL";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BackendType {
     MySql,
     Postgres,
@@ -21,6 +25,16 @@ impl<'a> From<&'a str> for BackendType {
     }
 }
 
+impl BackendType {
+    /// The backend of the database url in `MODELLER_DATABASE_URL`,
+    /// or `None` if the variable is not set.
+    pub fn from_env() -> Option<BackendType> {
+        std::env::var(DB_URL_KEY)
+            .ok()
+            .map(|url| BackendType::from(url.as_str()))
+    }
+}
+
 impl ToTokens for BackendType {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         use BackendType::*;
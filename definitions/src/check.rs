@@ -0,0 +1,36 @@
+use bincode::{Decode, Encode};
+
+use crate::backend_type::BackendType;
+
+/// A named `CHECK` constraint of a table.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct Check {
+    pub name: String,
+    pub expr: String,
+}
+
+impl Check {
+    /// Field checks are named `chk_{table}_{col}`, so migrations
+    /// can drop them on every backend.
+    pub fn constraint_name(table_name: &str, col: &str) -> String {
+        format!("chk_{table_name}_{col}")
+    }
+
+    pub fn constraint_sql(&self) -> String {
+        format!("CONSTRAINT {} CHECK ({})", self.name, self.expr)
+    }
+
+    pub fn drop_sql(&self, table_name: &str, bt: &BackendType) -> String {
+        match bt {
+            BackendType::MySql => format!("ALTER TABLE {table_name} DROP CHECK {};", self.name),
+            _ => format!(
+                "ALTER TABLE {table_name} DROP CONSTRAINT IF EXISTS {};",
+                self.name
+            ),
+        }
+    }
+
+    pub fn add_sql(&self, table_name: &str) -> String {
+        format!("ALTER TABLE {table_name} ADD {};", self.constraint_sql())
+    }
+}
@@ -1,76 +1,432 @@
+use crate::{
+    backend_type::BackendType, core::DefinitionContext, custom::CustomType, model::snake_case,
+};
 use bincode::{Decode, Encode};
 use quote::ToTokens;
-use syn::Type;
+use std::str::FromStr;
+use syn::{
+    Expr, ExprLit, Fields, GenericArgument, ItemEnum, Lit, PathArguments, PathSegment, Type,
+};
 
-#[derive(Debug, Default, Encode, Decode)]
+/// Size modifiers of a column type, e.g. the `12` in `VARCHAR(12)`
+/// or the `12,2` in `NUMERIC(12,2)`.
+#[derive(Debug, Default, Clone, PartialEq, Encode, Decode)]
+pub struct ColumnSize {
+    pub length: Option<usize>,
+    pub precision: Option<u32>,
+    pub scale: Option<u32>,
+}
+
+/// How UUIDs are stored on backends without a native UUID type.
+/// Postgres always uses `UUID`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Encode, Decode)]
+pub enum UuidStorage {
+    /// `CHAR(36)` on MySQL and `TEXT` on SQLite.
+    #[default]
+    Text,
+    /// `BINARY(16)` on MySQL and `BLOB` on SQLite.
+    Binary,
+}
+
+impl UuidStorage {
+    /// SQL expression that generates a random (v4) UUID on the database.
+    pub fn generate_sql(&self, bt: &BackendType) -> &'static str {
+        use BackendType::*;
+        use UuidStorage::*;
+
+        match (bt, self) {
+            (Postgres, _) => "gen_random_uuid()",
+            (MySql, Text) => "(UUID())",
+            (MySql, Binary) => "(UUID_TO_BIN(UUID()))",
+            (Sqlite, Text) => {
+                "(lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' || substr(hex(randomblob(2)), 2) || '-' || substr('89ab', 1 + (abs(random()) % 4), 1) || substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6))))"
+            }
+            (Sqlite, Binary) => "(randomblob(16))",
+        }
+    }
+}
+
+impl FromStr for UuidStorage {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(UuidStorage::Text),
+            "binary" => Ok(UuidStorage::Binary),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Encode, Decode)]
 pub(super) enum ColumnType {
     Int8,
     Int16,
     Int32,
     Int64,
+    /// unsigned integers. backends without unsigned types use a
+    /// type wide enough for every value, checked to be positive.
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Bool,
+    Float32,
+    Float64,
+    Decimal,
+    Uuid(UuidStorage),
+    Json,
+    Binary,
+    FixedBinary,
     Text,
     #[default]
     VarChar,
+    Date,
+    Time,
     Datetime,
+    DatetimeTz,
+    Enum {
+        name: String,
+        values: Vec<String>,
+    },
+    /// a user defined type, see `ModellerType`.
+    Custom(CustomType),
+    /// an SQL type modeller does not model, rendered verbatim.
+    Raw(String),
+    /// postgres only, see `ColumnType::unsupported_reason`.
+    Array(Box<ColumnType>),
     Nullable(Box<ColumnType>),
 }
 
 impl ColumnType {
-    pub fn to_sql(&self, len: &Option<usize>) -> String {
+    /// SQL type of the column, without its nullability.
+    pub fn type_sql(&self, size: &ColumnSize, bt: &BackendType) -> String {
+        bt.dialect().type_sql(self, size)
+    }
+
+    /// The storage of a `Uuid` column, or `None` for other types.
+    pub fn uuid_storage(&self) -> Option<UuidStorage> {
+        match self {
+            ColumnType::Uuid(storage) => Some(*storage),
+            ColumnType::Nullable(inner) => inner.uuid_storage(),
+            _ => None,
+        }
+    }
+
+    /// Changes the storage of a `Uuid` column. Returns `None` if
+    /// the column is not a `Uuid` column.
+    pub fn with_uuid_storage(self, storage: UuidStorage) -> Option<Self> {
+        match self {
+            ColumnType::Uuid(_) => Some(ColumnType::Uuid(storage)),
+            ColumnType::Nullable(inner) => inner
+                .with_uuid_storage(storage)
+                .map(|inner| ColumnType::Nullable(Box::new(inner))),
+            _ => None,
+        }
+    }
+
+    /// A `Json` column for a field of type `ty`, whatever the type
+    /// it holds, which is nullable if `ty` is an `Option`.
+    pub fn json(ty: &Type, ctx: &DefinitionContext) -> syn::Result<Self> {
+        ColumnType::Json.nullable_for(ty, ctx)
+    }
+
+    /// Wraps the column in `Nullable` if the field type `ty` is an `Option`.
+    pub fn nullable_for(self, ty: &Type, ctx: &DefinitionContext) -> syn::Result<Self> {
+        if is_option(ty, ctx)? {
+            Ok(self.into_nullable())
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// The type of the column, regardless of its nullability.
+    pub(crate) fn non_nullable(&self) -> &Self {
+        match self {
+            ColumnType::Nullable(inner) => inner,
+            col_type => col_type,
+        }
+    }
+
+    pub(crate) fn into_nullable(self) -> Self {
+        match self {
+            ColumnType::Nullable(_) => self,
+            _ => ColumnType::Nullable(Box::new(self)),
+        }
+    }
+
+    /// Column type of a rust field type. The type is analysed
+    /// structurally, so paths can be qualified (`std::string::String`),
+    /// wrapped in references or smart pointers (`&'static str`,
+    /// `Box<str>`) and refer to enums and type aliases in `ctx`.
+    pub fn from_type(ty: &Type, ctx: &DefinitionContext) -> syn::Result<Self> {
         use ColumnType::*;
 
-        let len_str = len.map(|v| format!("({v})")).unwrap_or(String::new());
+        let unsupported = || {
+            syn::Error::new_spanned(
+                ty,
+                format!(
+                    "unsupported field type \"{}\". set the column type with #[modeller(type = ...)] instead.",
+                    ty.to_token_stream()
+                ),
+            )
+        };
+
+        match resolve_alias(ty, ctx)? {
+            Type::Reference(reference) => ColumnType::from_type(&reference.elem, ctx),
+            Type::Slice(slice) if ColumnType::from_type(&slice.elem, ctx)? == UInt8 => Ok(Binary),
+            Type::Array(array) if ColumnType::from_type(&array.elem, ctx)? == UInt8 => {
+                Ok(FixedBinary)
+            }
+            Type::Path(path) if path.qself.is_none() => {
+                let segment = path.path.segments.last().ok_or_else(unsupported)?;
+                let name = segment.ident.to_string();
+
+                match (name.as_str(), first_type_arg(segment)) {
+                    ("Option", Some(inner)) => {
+                        Ok(ColumnType::from_type(inner, ctx)?.into_nullable())
+                    }
+                    ("Box" | "Rc" | "Arc" | "Cow", Some(inner)) => {
+                        ColumnType::from_type(inner, ctx)
+                    }
+                    ("Vec", Some(inner)) => match ColumnType::from_type(inner, ctx)? {
+                        UInt8 => Ok(Binary),
+                        inner => Ok(Array(Box::new(inner))),
+                    },
+                    _ => match path.path.get_ident().and_then(|ident| ctx.find_enum(ident)) {
+                        Some(item) => ColumnType::from_enum(item),
+                        None => ColumnType::from_type_name(&name).ok_or_else(unsupported),
+                    },
+                }
+            }
+            _ => Err(unsupported()),
+        }
+    }
 
-        let sql = match self {
-            VarChar => format!("{}{len_str}", self.to_str()),
-            _ => format!("{}", self.to_str()),
+    /// The model `M` of a `ForeignKey<M>` field, which can be an `Option`.
+    pub fn foreign_key_target(ty: &Type, ctx: &DefinitionContext) -> syn::Result<Option<String>> {
+        let Type::Path(path) = resolve_alias(ty, ctx)? else {
+            return Ok(None);
         };
+        let Some(segment) = path.path.segments.last() else {
+            return Ok(None);
+        };
+
+        match (segment.ident.to_string().as_str(), first_type_arg(segment)) {
+            ("Option", Some(inner)) => ColumnType::foreign_key_target(inner, ctx),
+            ("ForeignKey", Some(Type::Path(model))) => Ok(model
+                .path
+                .segments
+                .last()
+                .map(|segment| segment.ident.to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    /// An `Enum` column for a unit-only Rust enum. The column
+    /// accepts the names of the enum variants.
+    pub fn from_enum(item: &ItemEnum) -> syn::Result<Self> {
+        let mut values = Vec::new();
+
+        for variant in &item.variants {
+            if !matches!(variant.fields, Fields::Unit) {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    format!(
+                        "enum \"{}\" can only be used as a column type if all its variants are unit variants.",
+                        item.ident
+                    ),
+                ));
+            }
+
+            values.push(variant.ident.to_string());
+        }
+
+        if values.is_empty() {
+            return Err(syn::Error::new_spanned(
+                item,
+                format!(
+                    "enum \"{}\" requires at least one variant to be used as a column type.",
+                    item.ident
+                ),
+            ));
+        }
+
+        Ok(ColumnType::Enum {
+            name: snake_case(&item.ident.to_string()),
+            values,
+        })
+    }
 
+    /// The name and values of an `Enum` column, or `None` for other types.
+    pub fn enum_values(&self) -> Option<(&str, &[String])> {
         match self {
-            Nullable(_) => sql,
-            _ => format!("{sql} NOT NULL"),
+            ColumnType::Enum { name, values } => Some((name, values)),
+            ColumnType::Nullable(inner) => inner.enum_values(),
+            _ => None,
         }
     }
 
-    pub fn to_str(&self) -> &'static str {
+    pub fn custom_type(&self) -> Option<&CustomType> {
+        match self {
+            ColumnType::Custom(custom) => Some(custom),
+            ColumnType::Nullable(inner) => inner.custom_type(),
+            _ => None,
+        }
+    }
+
+    /// Replaces the custom type of the column with `custom`, which
+    /// may make the column nullable.
+    pub fn with_custom_type(self, custom: &CustomType) -> Self {
+        let col_type = match self {
+            ColumnType::Custom(_) => ColumnType::Custom(custom.clone()),
+            ColumnType::Nullable(inner) => {
+                ColumnType::Nullable(Box::new(inner.with_custom_type(custom)))
+            }
+            col_type => col_type,
+        };
+
+        if custom.nullable {
+            col_type.into_nullable()
+        } else {
+            col_type
+        }
+    }
+
+    pub fn is_fixed_binary(&self) -> bool {
+        match self {
+            ColumnType::FixedBinary => true,
+            ColumnType::Nullable(inner) => inner.is_fixed_binary(),
+            _ => false,
+        }
+    }
+
+    /// The length of a `[u8; N]` field type, which is
+    /// stored as a fixed-length binary column.
+    pub fn array_len(ty: &Type, ctx: &DefinitionContext) -> syn::Result<Option<usize>> {
+        match resolve_alias(ty, ctx)? {
+            Type::Array(array) => match &array.len {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Int(len), ..
+                }) => Ok(len.base10_parse().ok()),
+                _ => Ok(None),
+            },
+            Type::Reference(reference) => ColumnType::array_len(&reference.elem, ctx),
+            Type::Path(path) => match path.path.segments.last() {
+                Some(segment) if segment.ident == "Option" => match first_type_arg(segment) {
+                    Some(inner) => ColumnType::array_len(inner, ctx),
+                    None => Ok(None),
+                },
+                _ => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// Why the column cannot be stored on `bt`, or `None` if it can.
+    pub fn unsupported_reason(&self, bt: &BackendType) -> Option<&'static str> {
+        match self {
+            ColumnType::Array(_) if !matches!(bt, BackendType::Postgres) => Some(
+                "array columns are only supported on postgres. add #[modeller(json)] to store the array as json instead.",
+            ),
+            // sqlite integers are signed 64-bit and larger numbers lose precision
+            ColumnType::UInt64 if matches!(bt, BackendType::Sqlite) => Some(
+                "u64 values above i64::MAX cannot be stored exactly on sqlite. add #[modeller(signed)] to store the field as i64 instead.",
+            ),
+            ColumnType::Nullable(inner) => inner.unsupported_reason(bt),
+            _ => None,
+        }
+    }
+
+    /// Whether the column holds an unsigned integer that the backend
+    /// stores in a signed type, so it must be checked to be positive.
+    pub fn needs_unsigned_check(&self, bt: &BackendType) -> bool {
         use ColumnType::*;
 
         match self {
-            Int8 => "BIT",
-            Int16 => "SMALLINT",
-            Int32 => "INTEGER",
-            Int64 => "BIGINT",
-            Text => "TEXT",
-            VarChar => "VARCHAR",
-            Datetime => "TIMESTAMP",
-            Nullable(inner) => inner.to_str(),
+            UInt8 | UInt16 | UInt32 | UInt64 => !matches!(bt, BackendType::MySql),
+            Nullable(inner) => inner.needs_unsigned_check(bt),
+            _ => false,
         }
     }
 
-    pub fn from_type_str(ty: &str) -> Self {
+    /// The signed integer of the same width as an unsigned integer
+    /// column, or `None` if the column is not an unsigned integer.
+    pub fn signed(&self) -> Option<Self> {
         use ColumnType::*;
 
-        match ty {
-            "u64" | "i64" => Int64,
-            "u32" | "i32" => Int32,
-            "u16" | "i16" => Int16,
-            "u8" | "i8" => Int8,
+        match self {
+            UInt8 => Some(Int8),
+            UInt16 => Some(Int16),
+            UInt32 => Some(Int32),
+            UInt64 => Some(Int64),
+            Nullable(inner) => inner.signed().map(|inner| Nullable(Box::new(inner))),
+            _ => None,
+        }
+    }
+
+    pub fn is_varchar(&self) -> bool {
+        match self {
+            ColumnType::VarChar => true,
+            ColumnType::Nullable(inner) => inner.is_varchar(),
+            _ => false,
+        }
+    }
+
+    pub fn is_json(&self) -> bool {
+        match self {
+            ColumnType::Json => true,
+            ColumnType::Nullable(inner) => inner.is_json(),
+            _ => false,
+        }
+    }
+
+    /// Column type of a scalar rust type, by the last segment of its path.
+    fn from_type_name(name: &str) -> Option<Self> {
+        use ColumnType::*;
+
+        let col_type = match name {
+            "i64" => Int64,
+            "i32" => Int32,
+            "i16" => Int16,
+            "i8" => Int8,
+            "u64" => UInt64,
+            "u32" => UInt32,
+            "u16" => UInt16,
+            "u8" => UInt8,
+            "bool" => Bool,
+            "f32" => Float32,
+            "f64" => Float64,
+            "Decimal" => Decimal,
+            "Uuid" => Uuid(UuidStorage::default()),
+            "Value" => Json,
             "String" | "str" => VarChar,
             "Text" => Text,
-            "Timestamp" | "Datetime" => Datetime,
-            _ => panic!("ColumnDefinition not implemented for {ty}"),
-        }
+            "NaiveDate" | "Date" => Date,
+            "NaiveTime" | "Time" => Time,
+            "NaiveDateTime" | "PrimitiveDateTime" | "Timestamp" | "Datetime" => Datetime,
+            "OffsetDateTime" | "DateTime" => DatetimeTz,
+            _ => return None,
+        };
+
+        Some(col_type)
     }
 }
 
+/// Parses the SQL type name of a `type` attr. Names that mean different
+/// types on different backends follow Postgres, e.g. `REAL` is always a
+/// 4-byte float even though SQLite stores `f64` as `REAL` too, and
+/// `TIMESTAMP` has no time zone even though MySQL uses it for `DateTime`.
 impl<'a> From<&'a str> for ColumnType {
     fn from(ty: &'a str) -> Self {
         use ColumnType::*;
 
-        if ty.starts_with("NULLABLE") {
-            let split: Vec<&str> = ty.split(" ").collect();
-            if let Some(value) = split.get(1) {
-                let inner = Box::new(value.trim().into());
+        if let Some(value) = ty.strip_suffix("[]") {
+            Array(Box::new(value.trim().into()))
+        } else if let Some(value) = ty.strip_prefix("NULLABLE") {
+            let value = value.trim();
+            if !value.is_empty() {
+                let inner = Box::new(value.into());
                 Nullable(inner)
             } else {
                 panic!("provide field type for a nullable field")
@@ -78,31 +434,184 @@ impl<'a> From<&'a str> for ColumnType {
         } else {
             match ty {
                 "BIGINT" => Int64,
-                "INTEGER" => Int32,
+                "INTEGER" | "INT" => Int32,
                 "SMALLINT" => Int16,
-                "BIT" => Int8,
+                "TINYINT" | "BIT" => Int8,
+                "BIGINT UNSIGNED" => UInt64,
+                "INT UNSIGNED" | "INTEGER UNSIGNED" => UInt32,
+                "SMALLINT UNSIGNED" => UInt16,
+                "TINYINT UNSIGNED" => UInt8,
+                "BOOLEAN" | "BOOL" | "TINYINT(1)" => Bool,
+                "REAL" | "FLOAT" => Float32,
+                "DOUBLE" | "DOUBLE PRECISION" => Float64,
+                "DECIMAL" | "NUMERIC" => Decimal,
+                "UUID" => Uuid(UuidStorage::default()),
+                "JSON" | "JSONB" => Json,
+                "BYTEA" | "BLOB" | "VARBINARY" => Binary,
+                "BINARY" => FixedBinary,
                 "VARCHAR" => VarChar,
                 "TEXT" => Text,
-                "DATETIME" => Datetime,
-                _ => panic!("ColumnDefinition not implemented for {ty}"),
+                "DATE" => Date,
+                "TIME" => Time,
+                "DATETIME" | "TIMESTAMP" => Datetime,
+                "TIMESTAMPTZ" | "TIMESTAMP WITH TIME ZONE" => DatetimeTz,
+                _ => Raw(ty.to_string()),
             }
         }
     }
 }
 
-impl From<&Type> for ColumnType {
-    fn from(ty: &Type) -> Self {
+/// Maximum depth of type aliases referring to other aliases.
+const MAX_ALIAS_DEPTH: usize = 16;
+
+/// The type `ty` stands for, looking through parentheses and
+/// type aliases declared alongside the models.
+fn resolve_alias<'t>(ty: &'t Type, ctx: &DefinitionContext<'t>) -> syn::Result<&'t Type> {
+    let mut resolved = ty;
+
+    for _ in 0..MAX_ALIAS_DEPTH {
+        resolved = match resolved {
+            Type::Paren(paren) => &paren.elem,
+            Type::Group(group) => &group.elem,
+            Type::Path(path) if path.qself.is_none() => {
+                match path
+                    .path
+                    .get_ident()
+                    .and_then(|ident| ctx.find_alias(ident))
+                {
+                    Some(alias) => &alias.ty,
+                    None => return Ok(resolved),
+                }
+            }
+            _ => return Ok(resolved),
+        };
+    }
+
+    Err(syn::Error::new_spanned(
+        ty,
+        format!("type alias \"{}\" is recursive.", ty.to_token_stream()),
+    ))
+}
+
+/// The first type argument of a path segment, e.g. `T` in
+/// `Option<T>` or `Cow<'a, T>`.
+fn first_type_arg(segment: &PathSegment) -> Option<&Type> {
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn is_option(ty: &Type, ctx: &DefinitionContext) -> syn::Result<bool> {
+    match resolve_alias(ty, ctx)? {
+        Type::Path(path) => Ok(path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option")),
+        _ => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColumnType, UuidStorage};
+    use crate::backend_type::BackendType;
+
+    #[test]
+    fn test_type_names_round_trip() {
         use ColumnType::*;
-        let ty = ty.to_token_stream().to_string();
-        let ty = ty.trim();
-
-        if ty.starts_with("Option") {
-            let rem_opt = ty.trim_start_matches("Option < ");
-            let trimmed = rem_opt.trim_end_matches(" >");
-            let inner = Box::new(ColumnType::from_type_str(trimmed));
-            Nullable(inner)
-        } else {
-            ColumnType::from_type_str(ty)
+
+        let types = [
+            Int16,
+            Int32,
+            Int64,
+            Bool,
+            Float32,
+            Float64,
+            Decimal,
+            Uuid(UuidStorage::Text),
+            Json,
+            Binary,
+            Text,
+            VarChar,
+            Date,
+            Time,
+            Datetime,
+            DatetimeTz,
+            Nullable(Box::new(DatetimeTz)),
+            Array(Box::new(Int64)),
+        ];
+
+        for ty in types {
+            let name = ty.type_sql(&Default::default(), &BackendType::Postgres);
+            let name = match ty {
+                Nullable(_) => format!("NULLABLE {name}"),
+                _ => name.to_string(),
+            };
+
+            assert_eq!(ColumnType::from(name.as_str()), ty, "{name}");
+        }
+
+        // sqlite stores both float widths as REAL, which postgres reads as f32
+        let name = BackendType::Sqlite.dialect().type_name(&Float64);
+        assert_eq!(ColumnType::from(name), Float32);
+
+        // postgres has no fixed-length binary, tiny or unsigned integer types
+        for ty in [FixedBinary, Int8, UInt8, UInt16, UInt32, UInt64, Bool] {
+            let name = BackendType::MySql.dialect().type_name(&ty);
+            assert_eq!(ColumnType::from(name), ty, "{name}");
+        }
+
+        // every backend's spelling reads back as a type rendered the same way
+        let types = [
+            Int8,
+            Int16,
+            Int32,
+            Int64,
+            UInt8,
+            UInt16,
+            UInt32,
+            UInt64,
+            Bool,
+            Float32,
+            Float64,
+            Decimal,
+            Uuid(UuidStorage::Text),
+            Uuid(UuidStorage::Binary),
+            Json,
+            Binary,
+            FixedBinary,
+            Text,
+            VarChar,
+            Date,
+            Time,
+            Datetime,
+            DatetimeTz,
+        ];
+
+        for bt in [
+            BackendType::Postgres,
+            BackendType::MySql,
+            BackendType::Sqlite,
+        ] {
+            for ty in &types {
+                let name = ty.type_sql(&Default::default(), &bt);
+                let parsed = ColumnType::from(name.as_str());
+
+                match (&bt, ty) {
+                    // postgres reads TIMESTAMP without a time zone
+                    (BackendType::MySql, DatetimeTz) => assert_eq!(parsed, Datetime),
+                    _ => assert_eq!(
+                        parsed.type_sql(&Default::default(), &bt),
+                        name,
+                        "{bt:?} {ty:?}"
+                    ),
+                }
+            }
         }
     }
 }
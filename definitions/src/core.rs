@@ -1,11 +1,18 @@
 use bincode::config;
+use proc_macro2::{Span, TokenStream};
 use quote::{ToTokens, quote};
-use syn::{ItemStruct, Token, parse::Parse};
+use syn::{Ident, Item, ItemEnum, ItemStruct, ItemType, Path, Token, parse::Parse};
 
-use crate::{backend_type::BackendType, model::ModelDefinition};
+use crate::{
+    backend_type::{BackendType, DB_URL_KEY},
+    model::ModelDefinition,
+    relation::{join_tables, resolve_references},
+};
 
 pub struct DefinitionStream {
     items: Vec<ItemStruct>,
+    enums: Vec<ItemEnum>,
+    aliases: Vec<ItemType>,
 }
 
 impl DefinitionStream {
@@ -13,26 +20,174 @@ impl DefinitionStream {
         &self.items
     }
 
-    pub fn raw(&self) -> Result<Vec<u8>, String> {
-        let config = config::standard();
+    pub fn enums(&self) -> &[ItemEnum] {
+        &self.enums
+    }
+
+    pub fn aliases(&self) -> &[ItemType] {
+        &self.aliases
+    }
+
+    pub fn raw(&self) -> syn::Result<Vec<u8>> {
+        encode_models(&self.models()?)
+    }
+
+    pub fn models(&self) -> syn::Result<Vec<ModelDefinition>> {
+        // the backend is only known at compile time if the database
+        // url is set. otherwise models are checked when modeller runs.
+        let ctx = DefinitionContext {
+            enums: &self.enums,
+            aliases: &self.aliases,
+            bt: BackendType::from_env(),
+        };
         let mut defs: Vec<ModelDefinition> = Vec::new();
 
         for item in &self.items {
-            let def = ModelDefinition::from(item);
+            let def = ModelDefinition::parse(item, &ctx)?;
             let name_exists = defs.iter().find(|d| d.name() == def.name());
 
             if name_exists.is_some() {
-                return Err(format!(
-                    "duplicate table name \"{}\". tables cannot have duplicate names.",
-                    def.name()
+                return Err(syn::Error::new_spanned(
+                    &item.ident,
+                    format!(
+                        "duplicate table name \"{}\". tables cannot have duplicate names.",
+                        def.name()
+                    ),
                 ));
             }
 
             defs.push(def);
         }
 
-        let raw = bincode::encode_to_vec(&defs, config).map_err(|err| err.to_string())?;
-        Ok(raw)
+        resolve_references(&mut defs, &self.items)?;
+
+        for table in join_tables(&defs, &self.items)? {
+            if defs.iter().any(|d| d.name() == table.name()) {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    format!(
+                        "join table \"{}\" has the name of another table. name it with \"through\" instead.",
+                        table.name()
+                    ),
+                ));
+            }
+
+            defs.push(table);
+        }
+
+        Ok(defs)
+    }
+
+    /// Implements `modeller::Model` for the models with a single column
+    /// primary key, so `ForeignKey`s to them know the type of their key.
+    pub fn model_impls(&self) -> syn::Result<TokenStream> {
+        let models = self.models()?;
+        let mut impls = Vec::new();
+
+        for (item, model) in self.items.iter().zip(&models) {
+            if !item.generics.params.is_empty() {
+                continue;
+            }
+
+            let key =
+                item.fields.iter().zip(model.fields()).find(|(_, field)| {
+                    field.is_serial() || model.primary_key() == [field.col_name()]
+                });
+
+            if let Some((key, field)) = key {
+                // serial columns are `INT` whatever the field's type
+                let ty = match field.is_serial() {
+                    true => quote! { i32 },
+                    false => key.ty.to_token_stream(),
+                };
+                let ident = &item.ident;
+                impls.push(quote! {
+                    impl ::modeller::Model for #ident {
+                        type PrimaryKey = #ty;
+                    }
+                });
+            }
+        }
+
+        Ok(quote! { #(#impls)* })
+    }
+
+    /// Generates the `modeller_definition_streams` function, which
+    /// returns the encoded model definitions, or a `ParseError` of
+    /// `modeller` if they cannot be resolved.
+    ///
+    /// Fields mapped with `via` are resolved by the generated function
+    /// through their `ModellerType` impls, re-exported by `modeller`.
+    pub fn definitions_fn(&self) -> syn::Result<TokenStream> {
+        let models = self.models()?;
+        let bytes = encode_models(&models)?;
+
+        let mut custom_types: Vec<&str> = Vec::new();
+        for field in models.iter().flat_map(|model| model.fields()) {
+            if let Some(custom) = field.custom_type()
+                && !custom_types.contains(&custom.rust_type.as_str())
+            {
+                custom_types.push(&custom.rust_type);
+            }
+        }
+
+        // models are checked against the backend of the database url
+        // while expanding, so changing it must expand them again.
+        // `option_env!` makes cargo rebuild when the variable changes.
+        let track_url = quote! {
+            const _: Option<&str> = option_env!(#DB_URL_KEY);
+        };
+
+        if custom_types.is_empty() {
+            return Ok(quote! {
+                pub fn modeller_definition_streams() -> Result<Vec<u8>, ::modeller::errors::Error> {
+                    #track_url
+                    Ok(vec![#(#bytes),*])
+                }
+            });
+        }
+
+        let paths = custom_types
+            .iter()
+            .map(|name| syn::parse_str::<Path>(name))
+            .collect::<syn::Result<Vec<Path>>>()?;
+
+        Ok(quote! {
+            pub fn modeller_definition_streams() -> Result<Vec<u8>, ::modeller::errors::Error> {
+                use ::modeller::definitions::custom::{CustomType, resolve_custom_types};
+                #track_url
+
+                let raw: Vec<u8> = vec![#(#bytes),*];
+                resolve_custom_types(&raw, &[#(CustomType::of::<#paths>(#custom_types)),*])
+                    .map_err(::modeller::errors::Error::ParseError)
+            }
+        })
+    }
+}
+
+fn encode_models(models: &[ModelDefinition]) -> syn::Result<Vec<u8>> {
+    bincode::encode_to_vec(models, config::standard())
+        .map_err(|err| syn::Error::new(Span::call_site(), err.to_string()))
+}
+
+/// Items declared alongside the models in `define_models!`,
+/// which model fields can refer to.
+#[derive(Default)]
+pub struct DefinitionContext<'a> {
+    pub enums: &'a [ItemEnum],
+    pub aliases: &'a [ItemType],
+    pub bt: Option<BackendType>,
+}
+
+impl<'a> DefinitionContext<'a> {
+    pub fn find_enum(&self, ident: &Ident) -> Option<&ItemEnum> {
+        self.enums.iter().find(|item| &item.ident == ident)
+    }
+
+    pub fn find_alias(&self, ident: &Ident) -> Option<&'a ItemType> {
+        self.aliases
+            .iter()
+            .find(|item| &item.ident == ident && item.generics.params.is_empty())
     }
 }
 
@@ -51,29 +206,89 @@ impl Parse for DefinitionStream {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         // load model definitions
         let mut items = Vec::new();
+        let mut enums = Vec::new();
+        let mut aliases = Vec::new();
 
         while !input.is_empty() {
-            let item: ItemStruct = input.parse()?;
-            items.push(item);
+            match input.parse::<Item>()? {
+                Item::Struct(item) => items.push(item),
+                Item::Enum(item) => enums.push(item),
+                Item::Type(item) => aliases.push(item),
+                item => {
+                    return Err(syn::Error::new_spanned(
+                        item,
+                        "only structs, enums and type aliases can be defined in models.",
+                    ));
+                }
+            }
 
-            input.parse::<Token![,]>()?;
+            // items can be separated by commas
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+            }
         }
 
-        Ok(DefinitionStream { items })
+        Ok(DefinitionStream {
+            items,
+            enums,
+            aliases,
+        })
     }
 }
 
-impl ToTokens for DefinitionStream {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        let bytes = match self.raw() {
-            Ok(raw) => raw,
-            Err(err) => panic!("{}", err),
+#[cfg(test)]
+mod tests {
+    use crate::{backend_type::BackendType, core::DefinitionContext, model::ModelDefinition};
+    use syn::{ItemStruct, parse_quote};
+
+    #[test]
+    fn test_backend_checks() {
+        let item: ItemStruct = parse_quote! {
+            #[modeller(index(columns(name), where = "name IS NOT NULL"))]
+            struct Tagged {
+                name: Option<String>,
+                tags: Vec<i32>
+            }
         };
 
-        tokens.extend(quote! {
-            pub fn modeller_definition_streams() -> Vec<u8> {
-                vec![#(#bytes),*]
+        // without a backend at build time, models are only checked at runtime
+        let model = ModelDefinition::parse(&item, &DefinitionContext::default()).unwrap();
+        assert!(model.unsupported_reason(&BackendType::Postgres).is_none());
+        let reason = model.unsupported_reason(&BackendType::Sqlite).unwrap();
+        assert!(reason.starts_with(r#"field "tags""#));
+        let ctx = DefinitionContext {
+            bt: Some(BackendType::Sqlite),
+            ..Default::default()
+        };
+        assert!(ModelDefinition::parse(&item, &ctx).is_err());
+
+        let stream: crate::core::DefinitionStream = parse_quote! { struct User { id: i32 } };
+        let tokens = stream.definitions_fn().unwrap().to_string();
+        assert!(tokens.contains(r#"option_env ! ("MODELLER_DATABASE_URL")"#));
+    }
+
+    #[test]
+    fn test_model_impls() {
+        let stream: crate::core::DefinitionStream = parse_quote! {
+            struct User {
+                #[modeller(serial)]
+                id: u64
             }
-        });
+            struct Country {
+                #[modeller(primary_key)]
+                code: String
+            }
+        };
+        let tokens = stream.model_impls().unwrap().to_string();
+
+        // serial keys are `INT` columns, read as `i32`
+        assert!(
+            tokens.contains("for User { type PrimaryKey = i32 ; }"),
+            "{tokens}"
+        );
+        assert!(
+            tokens.contains("for Country { type PrimaryKey = String ; }"),
+            "{tokens}"
+        );
     }
 }
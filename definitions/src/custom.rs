@@ -0,0 +1,176 @@
+use bincode::{Decode, Encode, config};
+
+use crate::{backend_type::BackendType, model::ModelDefinition};
+
+/// Column mapping of a user defined type, e.g. a newtype like
+/// `struct UserId(i64)`. Fields holding the type are mapped with
+/// `#[modeller(via = UserId)]`.
+///
+/// ```ignore
+/// struct Cents(i64);
+///
+/// impl ModellerType for Cents {
+///     fn sql_type(_bt: &BackendType) -> String {
+///         "BIGINT".to_string()
+///     }
+///
+///     fn default_sql(_bt: &BackendType) -> Option<String> {
+///         Some("0".to_string())
+///     }
+/// }
+/// ```
+pub trait ModellerType {
+    /// SQL type of the column on `bt`, without its nullability.
+    fn sql_type(bt: &BackendType) -> String;
+
+    /// whether the column accepts `NULL` even if the field is not an `Option`.
+    fn nullable() -> bool {
+        false
+    }
+
+    /// Default value of the column on `bt`, as an SQL expression.
+    /// A `default` attr on the field takes precedence.
+    fn default_sql(_bt: &BackendType) -> Option<String> {
+        None
+    }
+}
+
+/// An SQL snippet for each backend.
+#[derive(Debug, Default, Clone, PartialEq, Encode, Decode)]
+pub struct BackendSql {
+    pub postgres: String,
+    pub mysql: String,
+    pub sqlite: String,
+}
+
+impl BackendSql {
+    pub fn new(f: impl Fn(&BackendType) -> String) -> Self {
+        BackendSql {
+            postgres: f(&BackendType::Postgres),
+            mysql: f(&BackendType::MySql),
+            sqlite: f(&BackendType::Sqlite),
+        }
+    }
+
+    pub fn get(&self, bt: &BackendType) -> &str {
+        match bt {
+            BackendType::Postgres => &self.postgres,
+            BackendType::MySql => &self.mysql,
+            BackendType::Sqlite => &self.sqlite,
+        }
+    }
+}
+
+/// Column mapping of a `ModellerType`. Fields with a `via` attr are
+/// parsed with an unresolved mapping naming the rust type, which is
+/// filled in when the models are loaded, see `resolve_custom_types`.
+#[derive(Debug, Default, Clone, PartialEq, Encode, Decode)]
+pub struct CustomType {
+    pub rust_type: String,
+    pub sql: BackendSql,
+    pub nullable: bool,
+    /// empty on backends without a default value.
+    pub default: BackendSql,
+}
+
+impl CustomType {
+    pub fn unresolved(rust_type: &str) -> Self {
+        CustomType {
+            rust_type: rust_type.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The mapping of `T`, for fields with `#[modeller(via = rust_type)]`.
+    pub fn of<T: ModellerType>(rust_type: &str) -> Self {
+        CustomType {
+            rust_type: rust_type.to_string(),
+            sql: BackendSql::new(T::sql_type),
+            nullable: T::nullable(),
+            default: BackendSql::new(|bt| T::default_sql(bt).unwrap_or_default()),
+        }
+    }
+}
+
+/// Fills in the mappings of the custom types in the encoded models
+/// `raw`. Called by the code `parse_models!` generates, since the
+/// `ModellerType` impls can only be run once the models are compiled.
+pub fn resolve_custom_types(raw: &[u8], types: &[CustomType]) -> Result<Vec<u8>, String> {
+    let config = config::standard();
+    let (mut models, _): (Vec<ModelDefinition>, usize) = bincode::decode_from_slice(raw, config)
+        .map_err(|err| format!("unable to decode model definitions: {err}"))?;
+
+    for model in &mut models {
+        for field in model.fields_mut() {
+            let Some(rust_type) = field.custom_type().map(|custom| custom.rust_type.clone()) else {
+                continue;
+            };
+
+            if let Some(custom) = types.iter().find(|custom| custom.rust_type == rust_type) {
+                field.resolve_custom_type(custom);
+            }
+        }
+    }
+
+    bincode::encode_to_vec(&models, config)
+        .map_err(|err| format!("unable to encode model definitions: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CustomType, ModellerType, resolve_custom_types};
+    use crate::{
+        backend_type::BackendType, core::DefinitionStream, model::ModelDefinition,
+        test_util::fields_sql,
+    };
+    use bincode::config;
+    use syn::parse_quote;
+
+    struct Email;
+
+    impl ModellerType for Email {
+        fn sql_type(bt: &BackendType) -> String {
+            match bt {
+                BackendType::Postgres => "CITEXT".to_string(),
+                _ => "VARCHAR(320)".to_string(),
+            }
+        }
+
+        fn default_sql(_bt: &BackendType) -> Option<String> {
+            Some("''".to_string())
+        }
+    }
+
+    #[test]
+    fn test_resolve_custom_types() {
+        let stream: DefinitionStream = parse_quote! {
+            struct Account {
+                #[modeller(via = Email)]
+                email: Email,
+                #[modeller(via = Email, default = NULL)]
+                backup_email: Option<Email>
+            }
+        };
+
+        let raw = stream.raw().unwrap();
+        let raw = resolve_custom_types(&raw, &[CustomType::of::<Email>("Email")]).unwrap();
+        assert!(resolve_custom_types(&raw[..raw.len() / 2], &[]).is_err());
+        let (models, _): (Vec<ModelDefinition>, usize) =
+            bincode::decode_from_slice(&raw, config::standard()).unwrap();
+
+        assert_eq!(
+            fields_sql(&models[0], &BackendType::Postgres),
+            vec![
+                "email CITEXT NOT NULL DEFAULT ''",
+                "backup_email CITEXT DEFAULT NULL"
+            ]
+        );
+        assert_eq!(
+            fields_sql(&models[0], &BackendType::MySql),
+            vec![
+                "email VARCHAR(320) NOT NULL DEFAULT ''",
+                "backup_email VARCHAR(320) DEFAULT NULL"
+            ]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use bincode::{Decode, Encode};
+use std::str::FromStr;
+
+use crate::{
+    backend_type::BackendType,
+    column::{ColumnSize, ColumnType, UuidStorage},
+};
+
+/// A `#[modeller(default = ...)]` value, checked against the
+/// column type when the model is parsed.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub enum DefaultValue {
+    Null,
+    Bool(bool),
+    /// an integer or decimal literal, rendered as is.
+    Number(String),
+    /// a string, quoted by the backend.
+    Text(String),
+    /// `@now`, the current date, time or timestamp of the column.
+    Now,
+    /// a default of a raw or custom type, rendered verbatim.
+    Sql(String),
+}
+
+impl DefaultValue {
+    /// Parses the tokens of a `default` attr for a column of
+    /// `col_type`. Strings can be given as literals or bare words,
+    /// e.g. `default = Lagos`. `@uuid` is handled by the field, as
+    /// it sets `default_uuid`.
+    pub(crate) fn parse(
+        value: &str,
+        col_type: &ColumnType,
+        size: &ColumnSize,
+    ) -> Result<Self, String> {
+        use ColumnType::*;
+
+        let value = value.trim();
+        let literal = syn::parse_str::<syn::LitStr>(value)
+            .ok()
+            .map(|lit| lit.value());
+        let nullable = matches!(col_type, Nullable(_));
+        let col_type = match col_type {
+            Nullable(inner) => inner,
+            col_type => col_type,
+        };
+
+        // the type of raw and custom columns is opaque
+        if let Raw(_) | Custom(_) = col_type {
+            return Ok(DefaultValue::Sql(literal.unwrap_or(value.to_string())));
+        }
+
+        if literal.is_none() && value.eq_ignore_ascii_case("NULL") {
+            return match nullable {
+                true => Ok(DefaultValue::Null),
+                false => Err("NULL is only a valid default of nullable columns.".to_string()),
+            };
+        }
+
+        if let Some(function) = value.strip_prefix('@') {
+            return match (function.trim(), col_type) {
+                ("now", Date | Time | Datetime | DatetimeTz) => Ok(DefaultValue::Now),
+                ("now", _) => Err("@now is only a valid default of date and time columns.".into()),
+                ("uuid", _) => Err("@uuid is only a valid default of uuid columns.".into()),
+                (function, _) => Err(format!(
+                    "unknown default function @{function}. expected @now or @uuid."
+                )),
+            };
+        }
+
+        let number = value.replace(' ', "");
+        let parsed = match col_type {
+            Int8 => fits::<i8>(&number),
+            Int16 => fits::<i16>(&number),
+            Int32 => fits::<i32>(&number),
+            Int64 => fits::<i64>(&number),
+            UInt8 => fits::<u8>(&number),
+            UInt16 => fits::<u16>(&number),
+            UInt32 => fits::<u32>(&number),
+            UInt64 => fits::<u64>(&number),
+            Float32 | Float64 | Decimal => number
+                .parse::<f64>()
+                .is_ok_and(f64::is_finite)
+                .then_some(DefaultValue::Number(number)),
+            Bool => match value {
+                "true" => Some(DefaultValue::Bool(true)),
+                "false" => Some(DefaultValue::Bool(false)),
+                _ => None,
+            },
+            Enum { values, .. } => {
+                let value = literal.unwrap_or(value.to_string());
+                if !values.contains(&value) {
+                    return Err(format!(
+                        r#""{value}" is not a value of the enum: {}."#,
+                        values.join(", ")
+                    ));
+                }
+                Some(DefaultValue::Text(value))
+            }
+            VarChar | Text => {
+                let value = literal.unwrap_or(value.to_string());
+                if let Some(len) = size.length
+                    && value.chars().count() > len
+                {
+                    return Err(format!("default is longer than the length of {len}."));
+                }
+                Some(DefaultValue::Text(value))
+            }
+            Uuid(UuidStorage::Text) | Json | Date | Time | Datetime | DatetimeTz | Array(_) => {
+                literal.map(DefaultValue::Text)
+            }
+            Uuid(UuidStorage::Binary) | Binary | FixedBinary => None,
+            Raw(_) | Custom(_) | Nullable(_) => unreachable!(),
+        };
+
+        parsed.ok_or_else(|| format!("{value} is not a valid default of the column type."))
+    }
+
+    pub(crate) fn sql(&self, col_type: &ColumnType, bt: &BackendType) -> String {
+        use BackendType::*;
+        use ColumnType::*;
+
+        let col_type = match col_type {
+            Nullable(inner) => inner,
+            col_type => col_type,
+        };
+
+        match (self, col_type, bt) {
+            (DefaultValue::Null, ..) => "NULL".to_string(),
+            (DefaultValue::Bool(value), _, Sqlite) => (*value as u8).to_string(),
+            (DefaultValue::Bool(value), ..) => value.to_string().to_uppercase(),
+            (DefaultValue::Number(value) | DefaultValue::Sql(value), ..) => value.clone(),
+            // MySQL only accepts expressions as defaults of TEXT and JSON columns
+            (DefaultValue::Text(value), Text | Json, MySql) => {
+                format!("({})", bt.dialect().quote_literal(value))
+            }
+            (DefaultValue::Text(value), ..) => bt.dialect().quote_literal(value),
+            (DefaultValue::Now, Date, MySql) => "(CURRENT_DATE)".to_string(),
+            (DefaultValue::Now, Time, MySql) => "(CURRENT_TIME)".to_string(),
+            (DefaultValue::Now, Date, _) => "CURRENT_DATE".to_string(),
+            (DefaultValue::Now, Time, _) => "CURRENT_TIME".to_string(),
+            (DefaultValue::Now, ..) => "CURRENT_TIMESTAMP".to_string(),
+        }
+    }
+
+    /// Whether the value is computed by the database when a row is
+    /// inserted, which SQLite cannot do for added columns.
+    pub fn is_generated(&self) -> bool {
+        matches!(self, DefaultValue::Now)
+    }
+}
+
+/// `value` as a number default, if it is an integer of type `N`.
+fn fits<N: FromStr>(value: &str) -> Option<DefaultValue> {
+    value
+        .parse::<N>()
+        .ok()
+        .map(|_| DefaultValue::Number(value.to_string()))
+}
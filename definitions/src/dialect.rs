@@ -0,0 +1,288 @@
+use crate::{
+    backend_type::BackendType,
+    column::{ColumnSize, ColumnType, UuidStorage},
+};
+
+/// How a backend spells columns. Everything that is written
+/// differently per backend goes through its dialect, so the same
+/// models produce valid DDL on every backend.
+pub(crate) trait Dialect {
+    fn backend(&self) -> BackendType;
+
+    /// Name of `col_type`, without its size or nullability.
+    fn type_name(&self, col_type: &ColumnType) -> &'static str;
+
+    /// SQL type of `col_type` with its size, without its nullability.
+    fn type_sql(&self, col_type: &ColumnType, size: &ColumnSize) -> String;
+
+    /// Definition of an autoincrementing primary key column `col`.
+    fn serial_sql(&self, col: &str) -> String;
+
+    /// Quotes `value` as an SQL string literal.
+    fn quote_literal(&self, value: &str) -> String {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+
+    /// Quotes and joins `values`, e.g. for `ENUM(...)` or `IN (...)`.
+    fn quote_list(&self, values: &[String]) -> String {
+        values
+            .iter()
+            .map(|value| self.quote_literal(value))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+impl BackendType {
+    pub(crate) fn dialect(&self) -> &'static dyn Dialect {
+        match self {
+            BackendType::Postgres => &PostgresDialect,
+            BackendType::MySql => &MySqlDialect,
+            BackendType::Sqlite => &SqliteDialect,
+        }
+    }
+}
+
+/// Length of `VARCHAR` columns without a `length` attr on MySQL,
+/// which requires one.
+const MYSQL_VARCHAR_LENGTH: usize = 255;
+
+pub(crate) struct PostgresDialect;
+pub(crate) struct MySqlDialect;
+pub(crate) struct SqliteDialect;
+
+impl Dialect for PostgresDialect {
+    fn backend(&self) -> BackendType {
+        BackendType::Postgres
+    }
+
+    fn type_name(&self, col_type: &ColumnType) -> &'static str {
+        use ColumnType::*;
+
+        match col_type {
+            Int8 | Int16 | UInt8 => "SMALLINT",
+            Int32 | UInt16 => "INTEGER",
+            Int64 | UInt32 => "BIGINT",
+            UInt64 => "NUMERIC(20)",
+            Bool => "BOOLEAN",
+            Float32 => "REAL",
+            Float64 => "DOUBLE PRECISION",
+            Decimal => "NUMERIC",
+            Uuid(_) => "UUID",
+            Json => "JSONB",
+            Binary | FixedBinary => "BYTEA",
+            Text => "TEXT",
+            VarChar => "VARCHAR",
+            Date => "DATE",
+            Time => "TIME",
+            Datetime => "TIMESTAMP",
+            DatetimeTz => "TIMESTAMPTZ",
+            // enums, custom and raw types are rendered by `type_sql`
+            Enum { .. } => "ENUM",
+            Custom(_) => "CUSTOM",
+            Raw(_) => "RAW",
+            Array(inner) | Nullable(inner) => self.type_name(inner),
+        }
+    }
+
+    fn type_sql(&self, col_type: &ColumnType, size: &ColumnSize) -> String {
+        match col_type {
+            ColumnType::Array(inner) => format!("{}[]", self.type_sql(inner, size)),
+            ColumnType::Enum { name, .. } => name.clone(),
+            _ => base_type_sql(self, col_type, size),
+        }
+    }
+
+    fn serial_sql(&self, col: &str) -> String {
+        format!("{col} INT GENERATED ALWAYS AS IDENTITY PRIMARY KEY")
+    }
+}
+
+impl Dialect for MySqlDialect {
+    fn backend(&self) -> BackendType {
+        BackendType::MySql
+    }
+
+    fn type_name(&self, col_type: &ColumnType) -> &'static str {
+        use ColumnType::*;
+
+        match col_type {
+            Int8 => "TINYINT",
+            Int16 => "SMALLINT",
+            Int32 => "INTEGER",
+            Int64 => "BIGINT",
+            UInt8 => "TINYINT UNSIGNED",
+            UInt16 => "SMALLINT UNSIGNED",
+            UInt32 => "INT UNSIGNED",
+            UInt64 => "BIGINT UNSIGNED",
+            Bool => "TINYINT(1)",
+            Float32 => "FLOAT",
+            Float64 => "DOUBLE",
+            Decimal => "DECIMAL",
+            Uuid(UuidStorage::Text) => "CHAR(36)",
+            Uuid(UuidStorage::Binary) => "BINARY(16)",
+            // arrays are rejected before any sql is generated
+            Json | Array(_) => "JSON",
+            Binary => "BLOB",
+            FixedBinary => "BINARY",
+            Text => "TEXT",
+            VarChar => "VARCHAR",
+            Date => "DATE",
+            Time => "TIME",
+            Datetime => "DATETIME",
+            DatetimeTz => "TIMESTAMP",
+            // enums, custom and raw types are rendered by `type_sql`
+            Enum { .. } => "ENUM",
+            Custom(_) => "CUSTOM",
+            Raw(_) => "RAW",
+            Nullable(inner) => self.type_name(inner),
+        }
+    }
+
+    fn type_sql(&self, col_type: &ColumnType, size: &ColumnSize) -> String {
+        match (col_type, size.length) {
+            (ColumnType::VarChar, None) => format!("VARCHAR({MYSQL_VARCHAR_LENGTH})"),
+            (ColumnType::Binary, Some(len)) => format!("VARBINARY({len})"),
+            (ColumnType::FixedBinary, Some(len)) => format!("BINARY({len})"),
+            (ColumnType::Enum { values, .. }, _) => format!("ENUM({})", self.quote_list(values)),
+            _ => base_type_sql(self, col_type, size),
+        }
+    }
+
+    fn serial_sql(&self, col: &str) -> String {
+        format!("{col} INT AUTO_INCREMENT PRIMARY KEY")
+    }
+
+    /// backslashes are escape characters in MySQL strings.
+    fn quote_literal(&self, value: &str) -> String {
+        format!("'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+    }
+}
+
+impl Dialect for SqliteDialect {
+    fn backend(&self) -> BackendType {
+        BackendType::Sqlite
+    }
+
+    fn type_name(&self, col_type: &ColumnType) -> &'static str {
+        use ColumnType::*;
+
+        match col_type {
+            Int8 | UInt8 | UInt16 | UInt32 | Bool => "INTEGER",
+            Int16 => "SMALLINT",
+            Int32 => "INTEGER",
+            Int64 => "BIGINT",
+            // rejected by `ColumnType::unsupported_reason`
+            UInt64 => "NUMERIC(20)",
+            Float32 | Float64 => "REAL",
+            Decimal => "NUMERIC",
+            Uuid(UuidStorage::Text) => "TEXT",
+            Uuid(UuidStorage::Binary) => "BLOB",
+            // arrays are rejected before any sql is generated
+            Json | Array(_) => "TEXT",
+            Binary | FixedBinary => "BLOB",
+            Text => "TEXT",
+            VarChar => "VARCHAR",
+            Date => "DATE",
+            Time => "TIME",
+            Datetime | DatetimeTz => "DATETIME",
+            // values of enums are checked by a constraint
+            Enum { .. } => "TEXT",
+            Custom(_) => "CUSTOM",
+            Raw(_) => "RAW",
+            Nullable(inner) => self.type_name(inner),
+        }
+    }
+
+    fn type_sql(&self, col_type: &ColumnType, size: &ColumnSize) -> String {
+        base_type_sql(self, col_type, size)
+    }
+
+    fn serial_sql(&self, col: &str) -> String {
+        format!("{col} INTEGER PRIMARY KEY AUTOINCREMENT")
+    }
+}
+
+/// Renders the parts of column types spelled the same on every backend.
+fn base_type_sql(dialect: &dyn Dialect, col_type: &ColumnType, size: &ColumnSize) -> String {
+    use ColumnType::*;
+
+    match col_type {
+        VarChar => match size.length {
+            Some(len) => format!("{}({len})", dialect.type_name(col_type)),
+            None => dialect.type_name(col_type).to_string(),
+        },
+        Decimal => match (size.precision, size.scale) {
+            (Some(p), Some(s)) => format!("{}({p},{s})", dialect.type_name(col_type)),
+            (Some(p), None) => format!("{}({p})", dialect.type_name(col_type)),
+            _ => dialect.type_name(col_type).to_string(),
+        },
+        Custom(custom) => custom.sql.get(&dialect.backend()).to_string(),
+        Raw(sql) => sql.clone(),
+        Nullable(inner) => dialect.type_sql(inner, size),
+        _ => dialect.type_name(col_type).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend_type::BackendType, model::ModelDefinition, test_util::fields_sql};
+    use syn::{ItemStruct, parse_quote};
+
+    #[test]
+    fn test_dialects() {
+        let model: ItemStruct = parse_quote! {
+            struct User {
+                #[modeller(serial)]
+                id: u32,
+                name: String,
+                #[modeller(length=64)]
+                nickname: Option<String>,
+                level: i8,
+                joined_at: chrono::DateTime<chrono::Utc>
+            }
+        };
+        let model = ModelDefinition::from(&model);
+
+        assert_eq!(
+            fields_sql(&model, &BackendType::Postgres),
+            vec![
+                "id INT GENERATED ALWAYS AS IDENTITY PRIMARY KEY",
+                "name VARCHAR NOT NULL",
+                "nickname VARCHAR(64)",
+                "level SMALLINT NOT NULL",
+                "joined_at TIMESTAMPTZ NOT NULL"
+            ]
+        );
+        assert_eq!(
+            fields_sql(&model, &BackendType::MySql),
+            vec![
+                "id INT AUTO_INCREMENT PRIMARY KEY",
+                "name VARCHAR(255) NOT NULL",
+                "nickname VARCHAR(64)",
+                "level TINYINT NOT NULL",
+                "joined_at TIMESTAMP NOT NULL"
+            ]
+        );
+        assert_eq!(
+            fields_sql(&model, &BackendType::Sqlite),
+            vec![
+                "id INTEGER PRIMARY KEY AUTOINCREMENT",
+                "name VARCHAR NOT NULL",
+                "nickname VARCHAR(64) CHECK (length(nickname) <= 64)",
+                "level INTEGER NOT NULL",
+                "joined_at DATETIME NOT NULL"
+            ]
+        );
+
+        let value = r"it's C:\";
+        assert_eq!(
+            BackendType::Postgres.dialect().quote_literal(value),
+            r"'it''s C:\'"
+        );
+        assert_eq!(
+            BackendType::MySql.dialect().quote_literal(value),
+            r"'it''s C:\\'"
+        );
+    }
+}
@@ -0,0 +1,1389 @@
+use crate::{
+    backend_type::BackendType,
+    check::Check,
+    field::FieldDefinition,
+    index::Index,
+    model::ModelDefinition,
+    relation::{Reference, dependency_order},
+};
+
+/// A change required to move the database schema from one
+/// version of the models to another.
+#[derive(Debug)]
+pub enum SchemaChange<'a> {
+    /// The foreign keys of the `deferred` columns are left out, as
+    /// they close a cycle and are added by a later `AddReferences`.
+    CreateTable {
+        model: &'a ModelDefinition,
+        deferred: Vec<&'a str>,
+    },
+    DropTable(&'a ModelDefinition),
+    AlterTable {
+        old: &'a ModelDefinition,
+        new: &'a ModelDefinition,
+        changes: Vec<ColumnChange<'a>>,
+    },
+    /// Foreign keys of tables created in a cycle, once they all exist.
+    AddReferences {
+        model: &'a ModelDefinition,
+        cols: Vec<&'a str>,
+    },
+    /// Foreign keys of tables dropped in a cycle, before any is dropped.
+    DropReferences {
+        model: &'a ModelDefinition,
+        cols: Vec<&'a str>,
+    },
+    /// The values of an enum type kept by name changed. Postgres types
+    /// are shared by every column of the same enum, so they change once,
+    /// before any table is created or altered, along with the `columns`
+    /// of existing tables that keep using the type, as `(table, field)`.
+    AlterEnum {
+        name: &'a str,
+        old: &'a [String],
+        new: &'a [String],
+        columns: Vec<(&'a str, &'a FieldDefinition)>,
+    },
+    /// An enum type no longer used once every table is altered and
+    /// dropped. `recreated` types were replaced by an `AlterEnum`, and
+    /// are dropped under their temporary name.
+    DropEnum {
+        name: &'a str,
+        recreated: bool,
+    },
+}
+
+/// A change to the columns or constraints of an existing table.
+#[derive(Debug)]
+pub enum ColumnChange<'a> {
+    Add(&'a FieldDefinition),
+    Drop(&'a FieldDefinition),
+    Alter {
+        old: &'a FieldDefinition,
+        new: &'a FieldDefinition,
+    },
+    /// The columns of the primary key changed. Either list
+    /// is empty when the table has no such primary key.
+    PrimaryKey {
+        old: &'a [String],
+        new: &'a [String],
+    },
+    /// The foreign key of column `col` changed, including
+    /// columns that were added or dropped.
+    ForeignKey {
+        col: &'a str,
+        old: Option<&'a Reference>,
+        new: Option<&'a Reference>,
+    },
+    /// An index was added, dropped or changed, matched by name.
+    Index {
+        old: Option<&'a Index>,
+        new: Option<&'a Index>,
+    },
+    /// A check constraint was added, dropped or changed, matched by name.
+    Check {
+        old: Option<Check>,
+        new: Option<Check>,
+    },
+}
+
+/// Compares two versions of the models and returns the changes
+/// required to move the schema from `old` to `new`.
+///
+/// Models are matched by table name and fields by column name, so a
+/// renamed table or column shows up as a drop followed by a create.
+///
+/// Tables are created first, after the tables they reference, then
+/// altered, and dropped last, before the tables they reference.
+pub fn diff_models<'a>(
+    old: &'a [ModelDefinition],
+    new: &'a [ModelDefinition],
+) -> Vec<SchemaChange<'a>> {
+    let created: Vec<&ModelDefinition> = new
+        .iter()
+        .filter(|model| !old.iter().any(|m| m.name() == model.name()))
+        .collect();
+    let dropped: Vec<&ModelDefinition> = old
+        .iter()
+        .filter(|model| !new.iter().any(|m| m.name() == model.name()))
+        .collect();
+    let mut changes = diff_enums(old, new);
+    changes.extend(create_models(&created));
+
+    for model in new {
+        match old.iter().find(|m| m.name() == model.name()) {
+            Some(prev) if prev != model => {
+                let column_changes = diff_fields(prev, model);
+                if !column_changes.is_empty() {
+                    changes.push(SchemaChange::AlterTable {
+                        old: prev,
+                        new: model,
+                        changes: column_changes,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    changes.extend(drop_models(&dropped));
+
+    // enum types outlive their columns until every table is altered
+    let recreated: Vec<SchemaChange> = changes
+        .iter()
+        .filter_map(|change| match change {
+            SchemaChange::AlterEnum { name, old, new, .. } if enum_recreated(old, new) => {
+                Some(SchemaChange::DropEnum {
+                    name,
+                    recreated: true,
+                })
+            }
+            _ => None,
+        })
+        .collect();
+    changes.extend(recreated);
+
+    let new_types = enum_types(new);
+    changes.extend(
+        enum_types(old)
+            .into_iter()
+            .filter(|(name, _)| !new_types.iter().any(|(n, _)| n == name))
+            .map(|(name, _)| SchemaChange::DropEnum {
+                name,
+                recreated: false,
+            }),
+    );
+    changes
+}
+
+/// The enum types used by the columns of `models`, once each.
+fn enum_types(models: &[ModelDefinition]) -> Vec<(&str, &[String])> {
+    let mut types: Vec<(&str, &[String])> = Vec::new();
+
+    for field in models.iter().flat_map(ModelDefinition::fields) {
+        if let Some((name, values)) = field.enum_values()
+            && !types.iter().any(|(n, _)| *n == name)
+        {
+            types.push((name, values));
+        }
+    }
+
+    types
+}
+
+/// Changes to the values of the enum types kept by name.
+fn diff_enums<'a>(old: &'a [ModelDefinition], new: &'a [ModelDefinition]) -> Vec<SchemaChange<'a>> {
+    let old_types = enum_types(old);
+    let mut changes = Vec::new();
+
+    for (name, values) in enum_types(new) {
+        let Some((_, old_values)) = old_types.iter().find(|(n, _)| *n == name) else {
+            continue;
+        };
+        if *old_values == values {
+            continue;
+        }
+
+        // columns of new tables and columns changing type are
+        // created or altered once the type is up to date
+        let mut columns = Vec::new();
+        for model in new {
+            let Some(prev) = old.iter().find(|m| m.name() == model.name()) else {
+                continue;
+            };
+
+            for field in model.fields() {
+                let uses_type =
+                    |field: &FieldDefinition| field.enum_values().is_some_and(|(n, _)| n == name);
+                if uses_type(field) && prev.field(field.col_name()).is_some_and(uses_type) {
+                    columns.push((model.name(), field));
+                }
+            }
+        }
+
+        changes.push(SchemaChange::AlterEnum {
+            name,
+            old: old_values,
+            new: values,
+            columns,
+        });
+    }
+
+    changes
+}
+
+/// Postgres can only add values to an enum, keeping the order of the
+/// existing values. Any other change recreates the type.
+fn enum_recreated(old: &[String], new: &[String]) -> bool {
+    !new.iter().filter(|v| old.contains(v)).eq(old.iter())
+}
+
+/// Creates the tables of `models` in dependency order.
+pub fn create_models<'a>(models: &[&'a ModelDefinition]) -> Vec<SchemaChange<'a>> {
+    let (order, deferred) = dependency_order(models);
+    let deferred_cols = |model: &ModelDefinition| -> Vec<&'a str> {
+        deferred
+            .iter()
+            .filter(|(m, _)| m.name() == model.name())
+            .map(|(_, col)| *col)
+            .collect()
+    };
+
+    let mut changes: Vec<SchemaChange> = order
+        .iter()
+        .map(|model| SchemaChange::CreateTable {
+            model,
+            deferred: deferred_cols(model),
+        })
+        .collect();
+
+    for model in &order {
+        let cols = deferred_cols(model);
+        if !cols.is_empty() {
+            changes.push(SchemaChange::AddReferences { model, cols });
+        }
+    }
+
+    changes
+}
+
+/// Drops the tables of `models`, in reverse dependency order.
+pub fn drop_models<'a>(models: &[&'a ModelDefinition]) -> Vec<SchemaChange<'a>> {
+    let (order, deferred) = dependency_order(models);
+    let mut changes = Vec::new();
+
+    for model in &order {
+        let cols: Vec<&str> = deferred
+            .iter()
+            .filter(|(m, _)| m.name() == model.name())
+            .map(|(_, col)| *col)
+            .collect();
+        if !cols.is_empty() {
+            changes.push(SchemaChange::DropReferences { model, cols });
+        }
+    }
+
+    changes.extend(
+        order
+            .iter()
+            .rev()
+            .map(|model| SchemaChange::DropTable(model)),
+    );
+    changes
+}
+
+fn diff_fields<'a>(old: &'a ModelDefinition, new: &'a ModelDefinition) -> Vec<ColumnChange<'a>> {
+    let mut changes = Vec::new();
+
+    for field in old.fields() {
+        if new.field(field.col_name()).is_none() {
+            changes.push(ColumnChange::Drop(field));
+        }
+    }
+
+    for field in new.fields() {
+        match old.field(field.col_name()) {
+            None => changes.push(ColumnChange::Add(field)),
+            Some(prev) if !prev.same_column(field) => changes.push(ColumnChange::Alter {
+                old: prev,
+                new: field,
+            }),
+            _ => {}
+        }
+    }
+
+    let columns = old.fields().iter().chain(
+        new.fields()
+            .iter()
+            .filter(|field| old.field(field.col_name()).is_none()),
+    );
+    for field in columns {
+        let col = field.col_name();
+        let old_ref = old.field(col).and_then(|f| f.references());
+        let new_ref = new.field(col).and_then(|f| f.references());
+        if old_ref != new_ref {
+            changes.push(ColumnChange::ForeignKey {
+                col,
+                old: old_ref,
+                new: new_ref,
+            });
+        }
+    }
+
+    let names = old.indexes().iter().chain(
+        new.indexes()
+            .iter()
+            .filter(|index| old.index(&index.name).is_none()),
+    );
+    for index in names {
+        let old_index = old.index(&index.name);
+        let new_index = new.index(&index.name);
+        if old_index != new_index {
+            changes.push(ColumnChange::Index {
+                old: old_index,
+                new: new_index,
+            });
+        }
+    }
+
+    let (old_checks, new_checks) = (old.checks(), new.checks());
+    let names = old_checks.iter().chain(
+        new_checks
+            .iter()
+            .filter(|check| !old_checks.iter().any(|c| c.name == check.name)),
+    );
+    for check in names {
+        let old_check = old_checks.iter().find(|c| c.name == check.name);
+        let new_check = new_checks.iter().find(|c| c.name == check.name);
+        if old_check != new_check {
+            changes.push(ColumnChange::Check {
+                old: old_check.cloned(),
+                new: new_check.cloned(),
+            });
+        }
+    }
+
+    if old.primary_key() != new.primary_key() {
+        changes.push(ColumnChange::PrimaryKey {
+            old: old.primary_key(),
+            new: new.primary_key(),
+        });
+    }
+
+    changes
+}
+
+/// A short snake_case description of `changes`, used to name
+/// their migration.
+pub fn describe_changes(changes: &[SchemaChange]) -> String {
+    changes
+        .iter()
+        .map(SchemaChange::describe)
+        .filter(|description| !description.is_empty())
+        .collect::<Vec<String>>()
+        .join("_and_")
+}
+
+impl SchemaChange<'_> {
+    /// A short snake_case description of the change,
+    /// e.g. `add_age_to_users`.
+    pub fn describe(&self) -> String {
+        use SchemaChange::*;
+
+        match self {
+            CreateTable { model, .. } => format!("create_{}", model.name()),
+            AlterEnum { name, .. } => format!("alter_enum_{name}"),
+            // part of the `AlterEnum` replacing the type
+            DropEnum {
+                recreated: true, ..
+            } => String::new(),
+            DropEnum { name, .. } => format!("drop_enum_{name}"),
+            AddReferences { model, .. } => format!("add_references_to_{}", model.name()),
+            DropReferences { model, .. } => format!("drop_references_from_{}", model.name()),
+            DropTable(model) => format!("drop_{}", model.name()),
+            AlterTable { new, changes, .. } => changes
+                .iter()
+                // keys of added and dropped columns are described by them
+                .filter(|change| match change.key_column() {
+                    Some(col) => !changes.iter().any(|other| {
+                        matches!(other, ColumnChange::Add(f) | ColumnChange::Drop(f) if f.col_name() == col)
+                    }),
+                    None => true,
+                })
+                .map(|change| change.describe(new.name()))
+                .collect::<Vec<String>>()
+                .join("_and_"),
+        }
+    }
+
+    pub fn to_sql(&self, bt: &BackendType) -> String {
+        use SchemaChange::*;
+
+        match self {
+            // sqlite does not check references until rows are written,
+            // and cannot add constraints to existing tables
+            CreateTable { model, deferred } => {
+                let deferred: &[&str] = match bt {
+                    BackendType::Sqlite => &[],
+                    _ => deferred,
+                };
+                let mut sqls = vec![model.create_sql_deferring(model.name(), deferred, bt)];
+                sqls.extend(model.indexes_sql(model.name(), bt));
+                sqls.join("\n")
+            }
+            DropTable(model) => format!("DROP TABLE {};", model.name()),
+            AlterTable { old, new, changes } => alter_table_sql(old, new, changes, bt),
+            AddReferences { .. } | DropReferences { .. } if *bt == BackendType::Sqlite => {
+                String::new()
+            }
+            AlterEnum { .. } | DropEnum { .. } if *bt != BackendType::Postgres => String::new(),
+            AlterEnum {
+                name,
+                old,
+                new,
+                columns,
+            } => postgres_enum_sql(name, old, new, columns).join("\n"),
+            DropEnum {
+                name,
+                recreated: true,
+            } => format!("DROP TYPE IF EXISTS {OLD_ENUM_PREFIX}{name};"),
+            DropEnum { name, .. } => format!("DROP TYPE IF EXISTS {name};"),
+            AddReferences { model, cols } => cols
+                .iter()
+                .filter_map(|col| {
+                    ColumnChange::ForeignKey {
+                        col,
+                        old: None,
+                        new: model.field(col).and_then(|field| field.references()),
+                    }
+                    .add_constraint_sql(model.name(), bt)
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+            DropReferences { model, cols } => cols
+                .iter()
+                .filter_map(|col| {
+                    ColumnChange::ForeignKey {
+                        col,
+                        old: model.field(col).and_then(|field| field.references()),
+                        new: None,
+                    }
+                    .drop_constraint_sql(model.name(), bt)
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+        }
+    }
+}
+
+impl ColumnChange<'_> {
+    fn describe(&self, table_name: &str) -> String {
+        use ColumnChange::*;
+
+        match self {
+            Add(field) => format!("add_{}_to_{table_name}", field.col_name()),
+            Drop(field) => format!("remove_{}_from_{table_name}", field.col_name()),
+            Alter { new, .. } => format!("alter_{}_in_{table_name}", new.col_name()),
+            PrimaryKey { new: [], .. } => {
+                format!("drop_primary_key_from_{table_name}")
+            }
+            PrimaryKey { .. } => format!("set_primary_key_on_{table_name}"),
+            ForeignKey { col, new: None, .. } => {
+                format!("drop_{col}_reference_from_{table_name}")
+            }
+            ForeignKey { col, .. } => format!("set_{col}_reference_in_{table_name}"),
+            Index {
+                new: Some(index), ..
+            } => format!("add_{}", index.name),
+            Index {
+                old: Some(index), ..
+            } => format!("drop_{}", index.name),
+            Index { .. } => String::new(),
+            Check {
+                new: Some(check), ..
+            } => format!("add_{}", check.name),
+            Check {
+                old: Some(check), ..
+            } => format!("drop_{}", check.name),
+            Check { .. } => String::new(),
+        }
+    }
+
+    /// The column covered by a foreign key or single column index change.
+    fn key_column(&self) -> Option<&str> {
+        match self {
+            ColumnChange::ForeignKey { col, .. } => Some(col),
+            ColumnChange::Index { old, new } => match new.or(*old)?.columns.as_slice() {
+                [col] => Some(col),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// SQLite can only add and drop plain columns. Anything else
+    /// requires the table to be rebuilt.
+    fn sqlite_can_alter(&self) -> bool {
+        use ColumnChange::*;
+
+        match self {
+            Add(field) => {
+                !field.is_unique()
+                    && !field.is_serial()
+                    && !field.has_generated_default()
+                    && (field.is_nullable() || field.default_sql(&BackendType::Sqlite).is_some())
+            }
+            Drop(field) => !field.is_unique() && !field.is_serial(),
+            Alter { .. } | PrimaryKey { .. } | ForeignKey { .. } | Check { .. } => false,
+            Index { .. } => true,
+        }
+    }
+
+    fn to_sql(&self, table_name: &str, bt: &BackendType) -> Vec<String> {
+        use BackendType::*;
+        use ColumnChange::*;
+
+        match self {
+            Add(field) => {
+                let mut sqls: Vec<String> = field.create_type_sql(bt).into_iter().collect();
+                sqls.push(format!(
+                    "ALTER TABLE {table_name} ADD COLUMN {};",
+                    field.to_sql(bt).trim()
+                ));
+                sqls
+            }
+            Drop(field) => vec![format!(
+                "ALTER TABLE {table_name} DROP COLUMN {};",
+                field.col_name()
+            )],
+            Alter { old, new } => match bt {
+                MySql => mysql_alter_column_sql(table_name, old, new, bt),
+                Postgres => postgres_alter_column_sql(table_name, old, new),
+                Sqlite => vec![],
+            },
+            // rendered around the other changes, see `alter_table_sql`
+            PrimaryKey { .. } | ForeignKey { .. } | Index { .. } | Check { .. } => vec![],
+        }
+    }
+
+    /// Drops the old constraint of a key change, before any column
+    /// it covers is altered or dropped.
+    fn drop_constraint_sql(&self, table_name: &str, bt: &BackendType) -> Option<String> {
+        use BackendType::*;
+
+        match (self, bt) {
+            (ColumnChange::PrimaryKey { old: [], .. }, _) => None,
+            (ColumnChange::PrimaryKey { .. }, MySql) => {
+                Some(format!("ALTER TABLE {table_name} DROP PRIMARY KEY;"))
+            }
+            // postgres names primary keys `{table}_pkey`
+            (ColumnChange::PrimaryKey { .. }, _) => Some(format!(
+                "ALTER TABLE {table_name} DROP CONSTRAINT IF EXISTS {table_name}_pkey;"
+            )),
+            (ColumnChange::ForeignKey { old: None, .. }, _) => None,
+            (ColumnChange::ForeignKey { col, .. }, MySql) => Some(format!(
+                "ALTER TABLE {table_name} DROP FOREIGN KEY {};",
+                Reference::constraint_name(table_name, col)
+            )),
+            (ColumnChange::ForeignKey { col, .. }, _) => Some(format!(
+                "ALTER TABLE {table_name} DROP CONSTRAINT IF EXISTS {};",
+                Reference::constraint_name(table_name, col)
+            )),
+            (
+                ColumnChange::Index {
+                    old: Some(index), ..
+                },
+                _,
+            ) if !index.is_implicit(bt) => Some(index.drop_sql(table_name, bt)),
+            (
+                ColumnChange::Check {
+                    old: Some(check), ..
+                },
+                _,
+            ) => Some(check.drop_sql(table_name, bt)),
+            _ => None,
+        }
+    }
+
+    /// Adds the new constraint of a key change, once the columns
+    /// it covers exist.
+    fn add_constraint_sql(&self, table_name: &str, bt: &BackendType) -> Option<String> {
+        match self {
+            ColumnChange::PrimaryKey { new: [], .. } => None,
+            ColumnChange::PrimaryKey { new, .. } => {
+                let columns = new.join(", ");
+                match bt {
+                    BackendType::MySql => Some(format!(
+                        "ALTER TABLE {table_name} ADD PRIMARY KEY ({columns});"
+                    )),
+                    _ => Some(format!(
+                        "ALTER TABLE {table_name} ADD CONSTRAINT {table_name}_pkey PRIMARY KEY ({columns});"
+                    )),
+                }
+            }
+            ColumnChange::ForeignKey {
+                col,
+                new: Some(reference),
+                ..
+            } => Some(format!(
+                "ALTER TABLE {table_name} ADD {};",
+                reference.constraint_sql(table_name, col)
+            )),
+            ColumnChange::Index {
+                new: Some(index), ..
+            } if !index.is_implicit(bt) => Some(index.create_sql(table_name)),
+            ColumnChange::Check {
+                new: Some(check), ..
+            } => Some(check.add_sql(table_name)),
+            _ => None,
+        }
+    }
+}
+
+fn alter_table_sql(
+    old: &ModelDefinition,
+    new: &ModelDefinition,
+    changes: &[ColumnChange],
+    bt: &BackendType,
+) -> String {
+    let sqlite = matches!(bt, BackendType::Sqlite);
+    if sqlite && changes.iter().any(|change| !change.sqlite_can_alter()) {
+        return rebuild_table_sql(old, new, bt);
+    }
+
+    let table_name = new.name();
+    changes
+        .iter()
+        .filter_map(|change| change.drop_constraint_sql(table_name, bt))
+        .chain(
+            changes
+                .iter()
+                .flat_map(|change| change.to_sql(table_name, bt)),
+        )
+        .chain(
+            changes
+                .iter()
+                .filter_map(|change| change.add_constraint_sql(table_name, bt)),
+        )
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Recreates the table with the new definition and copies over the
+/// columns that exist in both versions. Used for SQLite, which cannot
+/// alter existing columns. Foreign keys are off meanwhile, so dropping
+/// the old table does not delete or reject the rows referencing it, and
+/// are checked once the new table has taken its place. SQLite ignores
+/// `PRAGMA foreign_keys` within transactions, see `Modeller::exec_migration`.
+fn rebuild_table_sql(old: &ModelDefinition, new: &ModelDefinition, bt: &BackendType) -> String {
+    let table_name = new.name();
+    let tmp_name = format!("_modeller_new_{table_name}");
+    let columns: Vec<&str> = new
+        .fields()
+        .iter()
+        .map(|field| field.col_name())
+        .filter(|col| old.field(col).is_some())
+        .collect();
+    let columns = columns.join(", ");
+
+    // indexes are dropped with the old table, and their names
+    // are only free to create them again once it is gone
+    let mut sqls = vec![format!(
+        "PRAGMA foreign_keys=OFF;\n{}\nINSERT INTO {tmp_name} ({columns}) SELECT {columns} FROM {table_name};\nDROP TABLE {table_name};\nALTER TABLE {tmp_name} RENAME TO {table_name};",
+        new.create_sql(&tmp_name, bt)
+    )];
+    sqls.extend(new.indexes_sql(table_name, bt));
+    sqls.push("PRAGMA foreign_key_check;\nPRAGMA foreign_keys=ON;".to_string());
+    sqls.join("\n")
+}
+
+fn mysql_alter_column_sql(
+    table_name: &str,
+    old: &FieldDefinition,
+    new: &FieldDefinition,
+    bt: &BackendType,
+) -> Vec<String> {
+    let col = new.col_name();
+    let mut sqls = Vec::new();
+
+    // inline unique constraints are named after the column in MySQL.
+    // drop it so modifying the column does not create a duplicate.
+    if old.is_unique() {
+        sqls.push(format!("ALTER TABLE {table_name} DROP INDEX {col};"));
+    }
+
+    let drop_pk = if old.is_serial() && !new.is_serial() {
+        ", DROP PRIMARY KEY"
+    } else {
+        ""
+    };
+
+    sqls.push(format!(
+        "ALTER TABLE {table_name} MODIFY COLUMN {}{drop_pk};",
+        new.to_sql(bt).trim()
+    ));
+
+    sqls
+}
+
+fn postgres_alter_column_sql(
+    table_name: &str,
+    old: &FieldDefinition,
+    new: &FieldDefinition,
+) -> Vec<String> {
+    let col = new.col_name();
+    let alter = format!("ALTER TABLE {table_name} ALTER COLUMN {col}");
+    let mut sqls = Vec::new();
+
+    if old.is_serial() && !new.is_serial() {
+        sqls.push(format!("{alter} DROP IDENTITY;"));
+        sqls.push(format!(
+            "ALTER TABLE {table_name} DROP CONSTRAINT IF EXISTS {table_name}_pkey;"
+        ));
+    }
+
+    if new.is_serial() {
+        if !old.is_serial() {
+            sqls.push(format!("{alter} TYPE INT USING {col}::INT;"));
+            sqls.push(format!("{alter} DROP DEFAULT;"));
+            sqls.push(format!("{alter} SET NOT NULL;"));
+            sqls.push(format!("{alter} ADD GENERATED ALWAYS AS IDENTITY;"));
+            sqls.push(format!("ALTER TABLE {table_name} ADD PRIMARY KEY ({col});"));
+        }
+
+        return sqls;
+    }
+
+    let ty = new.type_sql(&BackendType::Postgres);
+    if old.is_serial() || old.type_sql(&BackendType::Postgres) != ty {
+        // values are cast to enums through their text representation
+        let using = match new.create_type_sql(&BackendType::Postgres) {
+            Some(create_type) => {
+                sqls.push(create_type);
+                format!("{col}::text::{ty}")
+            }
+            None => format!("{col}::{ty}"),
+        };
+        sqls.push(format!("{alter} TYPE {ty} USING {using};"));
+    }
+
+    if old.is_serial() || old.is_nullable() != new.is_nullable() {
+        let action = if new.is_nullable() { "DROP" } else { "SET" };
+        sqls.push(format!("{alter} {action} NOT NULL;"));
+    }
+
+    let default_sql = new.default_sql(&BackendType::Postgres);
+    if old.default_sql(&BackendType::Postgres) != default_sql {
+        match default_sql {
+            Some(value) => sqls.push(format!("{alter} SET DEFAULT {value};")),
+            None => sqls.push(format!("{alter} DROP DEFAULT;")),
+        }
+    }
+
+    // inline checks are named `{table}_{col}_check` by postgres
+    let has_check = new.has_unsigned_check(&BackendType::Postgres);
+    if old.has_unsigned_check(&BackendType::Postgres) != has_check {
+        let constraint = format!("{table_name}_{col}_check");
+        if has_check {
+            sqls.push(format!(
+                "ALTER TABLE {table_name} ADD CONSTRAINT {constraint} CHECK ({col} >= 0);"
+            ));
+        } else {
+            sqls.push(format!(
+                "ALTER TABLE {table_name} DROP CONSTRAINT IF EXISTS {constraint};"
+            ));
+        }
+    }
+
+    if old.is_unique() != new.is_unique() {
+        let constraint = format!("{table_name}_{col}_key");
+        if new.is_unique() {
+            sqls.push(format!(
+                "ALTER TABLE {table_name} ADD CONSTRAINT {constraint} UNIQUE ({col});"
+            ));
+        } else {
+            sqls.push(format!(
+                "ALTER TABLE {table_name} DROP CONSTRAINT IF EXISTS {constraint};"
+            ));
+        }
+    }
+
+    sqls
+}
+
+/// Temporary name of an enum type while it is recreated.
+const OLD_ENUM_PREFIX: &str = "_modeller_old_";
+
+/// Updates the values of the enum type `name`, used by `columns`.
+/// The old type of a recreated enum is dropped by a later `DropEnum`,
+/// once no dropped table or column uses it.
+fn postgres_enum_sql(
+    name: &str,
+    old: &[String],
+    new: &[String],
+    columns: &[(&str, &FieldDefinition)],
+) -> Vec<String> {
+    let dialect = BackendType::Postgres.dialect();
+
+    if !enum_recreated(old, new) {
+        return new
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !old.contains(value))
+            .map(|(i, value)| {
+                let before = new[i + 1..]
+                    .iter()
+                    .find(|next| old.contains(next))
+                    .map(|next| format!(" BEFORE {}", dialect.quote_literal(next)))
+                    .unwrap_or_default();
+
+                format!(
+                    "ALTER TYPE {name} ADD VALUE IF NOT EXISTS {}{before};",
+                    dialect.quote_literal(value)
+                )
+            })
+            .collect();
+    }
+
+    let mut sqls = vec![
+        format!("ALTER TYPE {name} RENAME TO {OLD_ENUM_PREFIX}{name};"),
+        format!("CREATE TYPE {name} AS ENUM ({});", dialect.quote_list(new)),
+    ];
+
+    // defaults of the old type cannot be cast to the new one
+    for (table_name, field) in columns {
+        let col = field.col_name();
+        let alter = format!("ALTER TABLE {table_name} ALTER COLUMN {col}");
+        let default_sql = field.default_sql(&BackendType::Postgres);
+
+        if default_sql.is_some() {
+            sqls.push(format!("{alter} DROP DEFAULT;"));
+        }
+        sqls.push(format!("{alter} TYPE {name} USING {col}::text::{name};"));
+        if let Some(value) = default_sql {
+            sqls.push(format!("{alter} SET DEFAULT {value};"));
+        }
+    }
+
+    sqls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe_changes, diff_models};
+    use crate::{
+        backend_type::BackendType,
+        core::{DefinitionContext, DefinitionStream},
+        model::ModelDefinition,
+    };
+    use syn::{ItemEnum, ItemStruct, parse_quote};
+
+    fn model(item: ItemStruct) -> ModelDefinition {
+        ModelDefinition::from(&item)
+    }
+
+    #[test]
+    fn test_diff_models() {
+        let old = vec![
+            model(parse_quote! {
+                struct User {
+                    id: u64,
+                    #[modeller(length=12)]
+                    username: String,
+                    bio: Option<String>,
+                    #[modeller(precision=12, scale=2)]
+                    balance: Decimal
+                }
+            }),
+            model(parse_quote! { struct Session { token: String } }),
+        ];
+        let new = vec![
+            model(parse_quote! {
+                struct User {
+                    id: u64,
+                    #[modeller(length=24)]
+                    username: String,
+                    age: Option<u32>,
+                    #[modeller(precision=14, scale=2)]
+                    balance: Decimal
+                }
+            }),
+            model(parse_quote! { struct Post { title: String } }),
+        ];
+
+        let changes = diff_models(&old, &new);
+        let described: Vec<String> = changes.iter().map(|c| c.describe()).collect();
+        assert_eq!(
+            described,
+            vec![
+                "create_post",
+                "remove_bio_from_user_and_alter_username_in_user_and_add_age_to_user_and_alter_balance_in_user",
+                "drop_session",
+            ]
+        );
+
+        let sql = changes[1].to_sql(&BackendType::Postgres);
+        assert_eq!(
+            sql,
+            "ALTER TABLE user DROP COLUMN bio;\nALTER TABLE user ALTER COLUMN username TYPE VARCHAR(24) USING username::VARCHAR(24);\nALTER TABLE user ADD COLUMN age BIGINT CHECK (age >= 0);\nALTER TABLE user ALTER COLUMN balance TYPE NUMERIC(14,2) USING balance::NUMERIC(14,2);"
+        );
+
+        let sql = changes[1].to_sql(&BackendType::MySql);
+        assert!(sql.ends_with("MODIFY COLUMN balance DECIMAL(14,2) NOT NULL;"));
+
+        let sql = changes[1].to_sql(&BackendType::Sqlite);
+        assert!(sql.starts_with("PRAGMA foreign_keys=OFF;\nCREATE TABLE _modeller_new_user ("));
+        assert!(sql.contains(
+            "INSERT INTO _modeller_new_user (id, username, balance) SELECT id, username, balance FROM user;"
+        ));
+
+        assert!(diff_models(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_diff_postgres_enums() {
+        let old = vec![model(parse_quote! {
+            struct Post {
+                #[modeller(enum(draft, published))]
+                status: String
+            }
+        })];
+        let added = vec![model(parse_quote! {
+            struct Post {
+                #[modeller(enum(draft, review, published, archived))]
+                status: String
+            }
+        })];
+        let removed = vec![model(parse_quote! {
+            struct Post {
+                #[modeller(enum(draft, archived))]
+                status: String
+            }
+        })];
+
+        let sql = diff_models(&old, &added)[0].to_sql(&BackendType::Postgres);
+        assert_eq!(
+            sql,
+            "ALTER TYPE post_status ADD VALUE IF NOT EXISTS 'review' BEFORE 'published';\nALTER TYPE post_status ADD VALUE IF NOT EXISTS 'archived';"
+        );
+
+        let changes = diff_models(&old, &removed);
+        let sql = changes[0].to_sql(&BackendType::Postgres);
+        assert!(sql.starts_with("ALTER TYPE post_status RENAME TO _modeller_old_post_status;"));
+        assert!(sql.ends_with(
+            "ALTER TABLE post ALTER COLUMN status TYPE post_status USING status::text::post_status;"
+        ));
+        assert_eq!(
+            changes.last().unwrap().to_sql(&BackendType::Postgres),
+            "DROP TYPE IF EXISTS _modeller_old_post_status;"
+        );
+    }
+
+    #[test]
+    fn test_diff_shared_enums() {
+        let role: ItemEnum = parse_quote! { enum Role { Admin, Member, Guest } };
+        let ctx = DefinitionContext {
+            enums: std::slice::from_ref(&role),
+            ..Default::default()
+        };
+        let parse = |item: ItemStruct| ModelDefinition::parse(&item, &ctx).unwrap();
+        let old = vec![
+            parse(parse_quote! {
+                struct User {
+                    #[modeller(default = Member)]
+                    role: Role,
+                    previous_role: Option<Role>
+                }
+            }),
+            parse(parse_quote! { struct Invite { role: Role } }),
+            parse(parse_quote! { struct Session { role: Role } }),
+        ];
+
+        let role: ItemEnum = parse_quote! { enum Role { Admin, Member } };
+        let ctx = DefinitionContext {
+            enums: std::slice::from_ref(&role),
+            ..Default::default()
+        };
+        let parse = |item: ItemStruct| ModelDefinition::parse(&item, &ctx).unwrap();
+        let new = vec![
+            parse(parse_quote! {
+                struct User {
+                    #[modeller(default = Member)]
+                    role: Role,
+                    previous_role: Option<Role>
+                }
+            }),
+            parse(parse_quote! { struct Invite { role: Role } }),
+        ];
+
+        let changes = diff_models(&old, &new);
+        assert_eq!(
+            describe_changes(&changes),
+            "alter_enum_role_and_alter_role_in_user_and_alter_previous_role_in_user_and_alter_role_in_invite_and_drop_session"
+        );
+        let sqls: Vec<String> = changes
+            .iter()
+            .map(|change| change.to_sql(&BackendType::Postgres))
+            .filter(|sql| !sql.is_empty())
+            .collect();
+        assert_eq!(
+            sqls,
+            vec![
+                "ALTER TYPE role RENAME TO _modeller_old_role;\nCREATE TYPE role AS ENUM ('Admin', 'Member');\nALTER TABLE user ALTER COLUMN role DROP DEFAULT;\nALTER TABLE user ALTER COLUMN role TYPE role USING role::text::role;\nALTER TABLE user ALTER COLUMN role SET DEFAULT 'Member';\nALTER TABLE user ALTER COLUMN previous_role TYPE role USING previous_role::text::role;\nALTER TABLE invite ALTER COLUMN role TYPE role USING role::text::role;",
+                "DROP TABLE session;",
+                "DROP TYPE IF EXISTS _modeller_old_role;"
+            ]
+        );
+        assert_eq!(changes[0].to_sql(&BackendType::MySql), "");
+
+        // the type goes with the last table using it
+        let changes = diff_models(&new, &[]);
+        assert_eq!(
+            describe_changes(&changes),
+            "drop_invite_and_drop_user_and_drop_enum_role"
+        );
+        assert_eq!(
+            changes.last().unwrap().to_sql(&BackendType::Postgres),
+            "DROP TYPE IF EXISTS role;"
+        );
+    }
+
+    #[test]
+    fn test_diff_primary_keys() {
+        let old = vec![model(parse_quote! {
+            struct Membership {
+                #[modeller(primary_key)]
+                user_id: i64,
+                group_id: i64,
+                role: Option<String>
+            }
+        })];
+        let new = vec![model(parse_quote! {
+            #[modeller(primary_key(user_id, group_id))]
+            struct Membership {
+                user_id: i64,
+                group_id: i64,
+                role: Option<String>
+            }
+        })];
+
+        assert_eq!(
+            new[0].create_sql("membership", &BackendType::Postgres),
+            "CREATE TABLE membership (\n\tuser_id BIGINT NOT NULL,\n\tgroup_id BIGINT NOT NULL,\n\trole VARCHAR,\n\tPRIMARY KEY (user_id, group_id)\n);"
+        );
+
+        let changes = diff_models(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].describe(), "set_primary_key_on_membership");
+        assert_eq!(
+            changes[0].to_sql(&BackendType::Postgres),
+            "ALTER TABLE membership DROP CONSTRAINT IF EXISTS membership_pkey;\nALTER TABLE membership ADD CONSTRAINT membership_pkey PRIMARY KEY (user_id, group_id);"
+        );
+        assert_eq!(
+            changes[0].to_sql(&BackendType::MySql),
+            "ALTER TABLE membership DROP PRIMARY KEY;\nALTER TABLE membership ADD PRIMARY KEY (user_id, group_id);"
+        );
+        assert!(
+            changes[0]
+                .to_sql(&BackendType::Sqlite)
+                .starts_with("PRAGMA foreign_keys=OFF;\nCREATE TABLE _modeller_new_membership (")
+        );
+
+        let dropped = vec![model(parse_quote! {
+            struct Membership {
+                user_id: i64,
+                group_id: i64,
+                role: Option<String>
+            }
+        })];
+        let changes = diff_models(&new, &dropped);
+        assert_eq!(changes[0].describe(), "drop_primary_key_from_membership");
+        assert_eq!(
+            changes[0].to_sql(&BackendType::MySql),
+            "ALTER TABLE membership DROP PRIMARY KEY;"
+        );
+
+        let invalid: ItemStruct = parse_quote! {
+            #[modeller(primary_key(user_id, role))]
+            struct Membership {
+                user_id: i64,
+                role: Option<String>
+            }
+        };
+        let err = ModelDefinition::parse(&invalid, &Default::default()).unwrap_err();
+        assert!(err.to_string().contains("cannot be nullable"));
+    }
+
+    #[test]
+    fn test_diff_foreign_keys() {
+        let user: ItemStruct = parse_quote! {
+            struct User {
+                #[modeller(serial)]
+                id: u64
+            }
+        };
+        let old: DefinitionStream = parse_quote! {
+            #user
+            struct Post {
+                #[modeller(serial)]
+                id: u64,
+                author_id: i32
+            }
+        };
+        let new: DefinitionStream = parse_quote! {
+            #user
+            struct Post {
+                #[modeller(serial)]
+                id: u64,
+                #[modeller(references = User.id, on_delete = cascade)]
+                author_id: i32,
+                #[modeller(references = user.id, on_delete = set_null)]
+                editor_id: Option<i32>
+            }
+        };
+        let (old, new) = (old.models().unwrap(), new.models().unwrap());
+
+        assert_eq!(
+            new[1].create_sql("post", &BackendType::Sqlite),
+            "CREATE TABLE post (\n\tid INTEGER PRIMARY KEY AUTOINCREMENT,\n\tauthor_id INTEGER NOT NULL,\n\teditor_id INTEGER,\n\tCONSTRAINT fk_post_author_id FOREIGN KEY (author_id) REFERENCES user (id) ON DELETE CASCADE,\n\tCONSTRAINT fk_post_editor_id FOREIGN KEY (editor_id) REFERENCES user (id) ON DELETE SET NULL\n);"
+        );
+
+        let changes = diff_models(&old, &new);
+        assert_eq!(
+            changes[0].describe(),
+            "add_editor_id_to_post_and_set_author_id_reference_in_post"
+        );
+        assert_eq!(
+            changes[0].to_sql(&BackendType::Postgres),
+            "ALTER TABLE post ADD COLUMN editor_id INTEGER;\nALTER TABLE post ADD CONSTRAINT fk_post_author_id FOREIGN KEY (author_id) REFERENCES user (id) ON DELETE CASCADE;\nALTER TABLE post ADD CONSTRAINT fk_post_editor_id FOREIGN KEY (editor_id) REFERENCES user (id) ON DELETE SET NULL;"
+        );
+
+        let changes = diff_models(&new, &old);
+        assert_eq!(
+            changes[0].to_sql(&BackendType::MySql),
+            "ALTER TABLE post DROP FOREIGN KEY fk_post_author_id;\nALTER TABLE post DROP FOREIGN KEY fk_post_editor_id;\nALTER TABLE post DROP COLUMN editor_id;"
+        );
+        assert!(
+            changes[0]
+                .to_sql(&BackendType::Sqlite)
+                .starts_with("PRAGMA foreign_keys=OFF;\nCREATE TABLE _modeller_new_post (")
+        );
+
+        let invalid: DefinitionStream = parse_quote! {
+            struct User {
+                id: u64
+            }
+            struct Post {
+                #[modeller(references = User.id)]
+                author_id: i32
+            }
+        };
+        let err = invalid.models().unwrap_err();
+        assert!(err.to_string().contains("neither a primary key nor unique"));
+
+        // serial keys are `INT`, so a `BIGINT` column cannot reference them
+        let mismatched: DefinitionStream = parse_quote! {
+            #user
+            struct Post {
+                #[modeller(references = User.id)]
+                author_id: Option<i64>
+            }
+        };
+        let err = mismatched.models().unwrap_err();
+        assert!(err.to_string().contains("does not have the type of"));
+    }
+
+    #[test]
+    fn test_dependency_order() {
+        let stream: DefinitionStream = parse_quote! {
+            struct Comment {
+                #[modeller(serial)]
+                id: u64,
+                #[modeller(references = Post.id)]
+                post_id: i32,
+                #[modeller(references = Comment.id)]
+                parent_id: Option<i32>
+            }
+            struct Post {
+                #[modeller(serial)]
+                id: u64,
+                #[modeller(references = User.id)]
+                author_id: i32
+            }
+            struct User {
+                #[modeller(serial)]
+                id: u64,
+                #[modeller(references = Post.id)]
+                pinned_post_id: Option<i32>
+            }
+            struct Tag {
+                #[modeller(serial)]
+                id: u64
+            }
+        };
+        let models = stream.models().unwrap();
+
+        let changes = diff_models(&[], &models);
+        let described: Vec<String> = changes.iter().map(|c| c.describe()).collect();
+        assert_eq!(
+            described,
+            vec![
+                "create_user",
+                "create_post",
+                "create_comment",
+                "create_tag",
+                "add_references_to_user"
+            ]
+        );
+
+        let sql = changes[0].to_sql(&BackendType::Postgres);
+        assert!(!sql.contains("fk_user_pinned_post_id"));
+        assert_eq!(
+            changes[4].to_sql(&BackendType::Postgres),
+            "ALTER TABLE user ADD CONSTRAINT fk_user_pinned_post_id FOREIGN KEY (pinned_post_id) REFERENCES post (id);"
+        );
+        assert!(
+            changes[0]
+                .to_sql(&BackendType::Sqlite)
+                .contains("fk_user_pinned_post_id")
+        );
+        assert_eq!(changes[4].to_sql(&BackendType::Sqlite), "");
+
+        let changes = diff_models(&models, &[]);
+        let described: Vec<String> = changes.iter().map(|c| c.describe()).collect();
+        assert_eq!(
+            described,
+            vec![
+                "drop_references_from_user",
+                "drop_tag",
+                "drop_comment",
+                "drop_post",
+                "drop_user"
+            ]
+        );
+        assert_eq!(
+            changes[0].to_sql(&BackendType::MySql),
+            "ALTER TABLE user DROP FOREIGN KEY fk_user_pinned_post_id;"
+        );
+    }
+
+    #[test]
+    fn test_diff_indexes() {
+        let user: ItemStruct = parse_quote! {
+            struct User {
+                #[modeller(serial)]
+                id: u64
+            }
+        };
+        let old: DefinitionStream = parse_quote! {
+            #user
+            struct Post {
+                title: String
+            }
+        };
+        let new: DefinitionStream = parse_quote! {
+            #user
+            struct Post {
+                title: String,
+                author: Option<ForeignKey<User>>
+            }
+        };
+        let (old, new) = (old.models().unwrap(), new.models().unwrap());
+
+        let changes = diff_models(&old, &new);
+        assert_eq!(changes[0].describe(), "add_author_id_to_post");
+        assert_eq!(
+            changes[0].to_sql(&BackendType::Sqlite),
+            "PRAGMA foreign_keys=OFF;\nCREATE TABLE _modeller_new_post (\n\ttitle VARCHAR NOT NULL,\n\tauthor_id INTEGER,\n\tCONSTRAINT fk_post_author_id FOREIGN KEY (author_id) REFERENCES user (id)\n);\nINSERT INTO _modeller_new_post (title) SELECT title FROM post;\nDROP TABLE post;\nALTER TABLE _modeller_new_post RENAME TO post;\nCREATE INDEX idx_post_author_id ON post (author_id);\nPRAGMA foreign_key_check;\nPRAGMA foreign_keys=ON;"
+        );
+
+        // innodb drops the index of the foreign key with its column
+        let changes = diff_models(&new, &old);
+        assert_eq!(
+            changes[0].to_sql(&BackendType::MySql),
+            "ALTER TABLE post DROP FOREIGN KEY fk_post_author_id;\nALTER TABLE post DROP COLUMN author_id;"
+        );
+        assert_eq!(
+            changes[0].to_sql(&BackendType::Postgres),
+            "ALTER TABLE post DROP CONSTRAINT IF EXISTS fk_post_author_id;\nDROP INDEX IF EXISTS idx_post_author_id;\nALTER TABLE post DROP COLUMN author_id;"
+        );
+    }
+
+    #[test]
+    fn test_diff_model_indexes() {
+        let old = vec![model(parse_quote! {
+            struct Event {
+                #[modeller(index)]
+                kind: String,
+                tenant: i64,
+                at: chrono::NaiveDateTime
+            }
+        })];
+        let new = vec![model(parse_quote! {
+            #[modeller(index(columns(tenant, at)), index(name = event_kind, columns(kind), unique))]
+            struct Event {
+                kind: String,
+                tenant: i64,
+                at: chrono::NaiveDateTime
+            }
+        })];
+
+        let changes = diff_models(&old, &new);
+        assert_eq!(
+            changes[0].describe(),
+            "drop_idx_event_kind_and_add_idx_event_tenant_at_and_add_event_kind"
+        );
+        assert_eq!(
+            changes[0].to_sql(&BackendType::Postgres),
+            "DROP INDEX IF EXISTS idx_event_kind;\nCREATE INDEX idx_event_tenant_at ON event (tenant, at);\nCREATE UNIQUE INDEX event_kind ON event (kind);"
+        );
+        assert_eq!(
+            changes[0].to_sql(&BackendType::Sqlite),
+            changes[0].to_sql(&BackendType::Postgres)
+        );
+
+        let invalid: ItemStruct = parse_quote! {
+            #[modeller(index(columns(tenant, missing)))]
+            struct Event {
+                tenant: i64
+            }
+        };
+        let err = ModelDefinition::parse(&invalid, &Default::default()).unwrap_err();
+        assert!(err.to_string().contains(r#"index column "missing""#));
+    }
+
+    #[test]
+    fn test_diff_checks() {
+        let old = vec![model(parse_quote! {
+            struct Member {
+                age: i32,
+                #[modeller(length = 16)]
+                handle: String
+            }
+        })];
+        let new = vec![model(parse_quote! {
+            #[modeller(check(name = chk_member_adult, expr = "age >= 18 OR handle IS NULL"))]
+            struct Member {
+                #[modeller(check = "age >= 0")]
+                age: i32,
+                #[modeller(length = 16)]
+                handle: String
+            }
+        })];
+
+        assert_eq!(
+            new[0].create_sql("member", &BackendType::Postgres),
+            "CREATE TABLE member (\n\tage INTEGER NOT NULL,\n\thandle VARCHAR(16) NOT NULL,\n\tCONSTRAINT chk_member_age CHECK (age >= 0),\n\tCONSTRAINT chk_member_adult CHECK (age >= 18 OR handle IS NULL)\n);"
+        );
+
+        let changes = diff_models(&old, &new);
+        assert_eq!(
+            changes[0].describe(),
+            "add_chk_member_age_and_add_chk_member_adult"
+        );
+        assert_eq!(
+            changes[0].to_sql(&BackendType::Postgres),
+            "ALTER TABLE member ADD CONSTRAINT chk_member_age CHECK (age >= 0);\nALTER TABLE member ADD CONSTRAINT chk_member_adult CHECK (age >= 18 OR handle IS NULL);"
+        );
+        assert!(
+            changes[0]
+                .to_sql(&BackendType::Sqlite)
+                .starts_with("PRAGMA foreign_keys=OFF;\nCREATE TABLE _modeller_new_member (\n\tage INTEGER NOT NULL,\n\thandle VARCHAR(16) NOT NULL CHECK (length(handle) <= 16),")
+        );
+
+        let changes = diff_models(&new, &old);
+        assert_eq!(
+            changes[0].to_sql(&BackendType::MySql),
+            "ALTER TABLE member DROP CHECK chk_member_age;\nALTER TABLE member DROP CHECK chk_member_adult;"
+        );
+
+        let duplicate: ItemStruct = parse_quote! {
+            #[modeller(check(name = chk_member_age, expr = "age < 150"))]
+            struct Member {
+                #[modeller(check = "age >= 0")]
+                age: i32
+            }
+        };
+        let err = ModelDefinition::parse(&duplicate, &Default::default()).unwrap_err();
+        assert!(err.to_string().contains("two checks"));
+    }
+}
@@ -1,105 +1,1190 @@
 use crate::backend_type::BackendType;
-use crate::column::ColumnType;
+use crate::column::{ColumnSize, ColumnType};
+use crate::core::DefinitionContext;
+use crate::custom::{BackendSql, CustomType};
+use crate::default::DefaultValue;
+use crate::relation::{Reference, ReferentialAction};
 use bincode::{Decode, Encode};
+use proc_macro2::{TokenStream, TokenTree};
 use quote::ToTokens;
-use syn::{Field, Meta};
+use std::str::FromStr;
+use syn::{
+    Field, Ident, LitStr, Token, ext::IdentExt, meta::ParseNestedMeta, parenthesized,
+    parse::ParseStream,
+};
 
-#[derive(Debug, Default, Encode, Decode)]
+#[derive(Debug, Default, Clone, PartialEq, Encode, Decode)]
 pub struct FieldDefinition {
     col_name: String,
     col_type: ColumnType,
     serial: bool, // autoincrement field
+    primary_key: bool,
     unique: bool,
-    default_value: Option<String>,
-    length: Option<usize>,
+    indexed: bool,
+    default_value: Option<DefaultValue>,
+    default_uuid: bool,            // generate uuids on the database
+    check_json: bool,              // validate json text on sqlite
+    check: Option<String>,         // rendered as a named table constraint
+    references: Option<Reference>, // foreign key target
+    typed_relation: bool,          // `belongs_to` with its own type, until resolved
+    size: ColumnSize,
+    type_overrides: BackendSql, // empty on backends without an override
 }
 
 impl FieldDefinition {
+    pub fn col_name(&self) -> &str {
+        &self.col_name
+    }
+
+    pub fn is_serial(&self) -> bool {
+        self.serial
+    }
+
+    /// whether the column is part of the primary key of its table,
+    /// other than as a serial column.
+    pub fn is_primary_key(&self) -> bool {
+        self.primary_key
+    }
+
+    pub(crate) fn set_primary_key(&mut self, primary_key: bool) {
+        self.primary_key = primary_key;
+    }
+
+    /// whether the column has its own index, see `ModelDefinition::indexes`.
+    pub fn is_indexed(&self) -> bool {
+        self.indexed
+    }
+
+    /// whether both fields define the same column. Keys and indexes are
+    /// constraints of the table, so they are compared separately.
+    pub(crate) fn same_column(&self, other: &FieldDefinition) -> bool {
+        self == &FieldDefinition {
+            primary_key: self.primary_key,
+            indexed: self.indexed,
+            check: self.check.clone(),
+            references: self.references.clone(),
+            ..other.clone()
+        }
+    }
+
+    /// The row of another model the column refers to, if any.
+    pub fn references(&self) -> Option<&Reference> {
+        self.references.as_ref()
+    }
+
+    /// Points the foreign key of the column at the table of its model.
+    pub(crate) fn resolve_reference(&mut self, table: String) {
+        if let Some(reference) = &mut self.references {
+            reference.table = table;
+        }
+    }
+
+    /// whether the column is a relation to another model, declared with
+    /// a `ForeignKey<M>` type or `belongs_to`, before it is resolved.
+    pub(crate) fn is_unresolved_relation(&self) -> bool {
+        self.references
+            .as_ref()
+            .is_some_and(|reference| reference.column.is_empty())
+    }
+
+    /// Whether the unresolved relation declares the type of its column,
+    /// which has to match the key it references. `ForeignKey<M>` fields
+    /// take the type of the key instead.
+    pub(crate) fn is_typed_relation(&self) -> bool {
+        self.typed_relation
+    }
+
+    /// A column of a join table, referencing the primary key `key`
+    /// of `table`.
+    pub(crate) fn join_column(col_name: String, table: &str, key: &FieldDefinition) -> Self {
+        let mut field = FieldDefinition {
+            col_name,
+            primary_key: true,
+            references: Some(Reference {
+                table: table.to_string(),
+                column: String::new(),
+                on_delete: Some(ReferentialAction::Cascade),
+                on_update: None,
+            }),
+            ..Default::default()
+        };
+
+        field.resolve_relation(table.to_string(), key);
+        field
+    }
+
+    /// Points a relation at the primary key `target` of the model
+    /// in `table`, and stores the column with the same type.
+    pub(crate) fn resolve_relation(&mut self, table: String, target: &FieldDefinition) {
+        let col_type = target.key_type();
+        self.col_type = match self.is_nullable() {
+            true => col_type.into_nullable(),
+            false => col_type,
+        };
+        self.size = target.size.clone();
+        self.type_overrides = target.type_overrides.clone();
+        self.typed_relation = false;
+
+        if let Some(reference) = &mut self.references {
+            reference.table = table;
+            reference.column = target.col_name.clone();
+        }
+    }
+
+    /// Type of the columns referencing this one.
+    fn key_type(&self) -> ColumnType {
+        // serial columns are `INT` on every backend
+        match self.serial {
+            true => ColumnType::Int32,
+            false => self.col_type.non_nullable().clone(),
+        }
+    }
+
+    /// Whether the column has the type of the key `target` it
+    /// references, regardless of their nullability.
+    pub(crate) fn matches_key(&self, target: &FieldDefinition) -> bool {
+        *self.col_type.non_nullable() == target.key_type()
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        matches!(self.col_type, ColumnType::Nullable(_))
+    }
+
+    /// SQL type of the column, without its nullability and constraints.
+    pub fn type_sql(&self, bt: &BackendType) -> String {
+        match self.type_overrides.get(bt) {
+            "" => self.col_type.type_sql(&self.size, bt),
+            sql => sql.to_string(),
+        }
+    }
+
+    /// whether the default value is generated by a database function.
+    pub fn has_generated_default(&self) -> bool {
+        self.default_uuid
+            || self
+                .default_value
+                .as_ref()
+                .is_some_and(DefaultValue::is_generated)
+    }
+
+    pub fn default_sql(&self, bt: &BackendType) -> Option<String> {
+        if self.default_uuid {
+            let storage = self.col_type.uuid_storage().unwrap_or_default();
+            return Some(storage.generate_sql(bt).to_string());
+        }
+
+        let Some(value) = &self.default_value else {
+            let default = self.custom_type()?.default.get(bt);
+            return (!default.is_empty()).then(|| default.to_string());
+        };
+
+        Some(value.sql(&self.col_type, bt))
+    }
+
+    pub fn custom_type(&self) -> Option<&CustomType> {
+        self.col_type.custom_type()
+    }
+
+    /// Fills in the mapping of the custom type of the column.
+    pub(crate) fn resolve_custom_type(&mut self, custom: &CustomType) {
+        self.col_type = std::mem::take(&mut self.col_type).with_custom_type(custom);
+    }
+
+    /// The name and values of the enum type of the column, if any.
+    pub fn enum_values(&self) -> Option<(&str, &[String])> {
+        self.col_type.enum_values()
+    }
+
+    /// whether the column is an unsigned integer checked to be positive.
+    pub fn has_unsigned_check(&self, bt: &BackendType) -> bool {
+        self.col_type.needs_unsigned_check(bt)
+    }
+
+    /// The `check` attr of the column, see `ModelDefinition::checks`.
+    pub fn check(&self) -> Option<&str> {
+        self.check.as_deref()
+    }
+
+    /// inline `CHECK` constraints of the column.
+    fn checks_sql(&self, bt: &BackendType) -> Vec<String> {
+        let col = &self.col_name;
+        let mut checks = Vec::new();
+
+        if self.has_unsigned_check(bt) {
+            checks.push(format!("CHECK ({col} >= 0)"));
+        }
+
+        if let BackendType::Sqlite = bt {
+            if self.check_json {
+                checks.push(format!("CHECK (json_valid({col}))"));
+            }
+
+            // sqlite does not enforce the length of `VARCHAR(n)`
+            if let Some(len) = self.size.length
+                && self.col_type.is_varchar()
+                && self.type_overrides.get(bt).is_empty()
+            {
+                checks.push(format!("CHECK (length({col}) <= {len})"));
+            }
+
+            // sqlite has no enum type, so values are checked instead
+            if let Some((_, values)) = self.enum_values() {
+                let values = bt.dialect().quote_list(values);
+                checks.push(format!("CHECK ({col} IN ({values}))"));
+            }
+        }
+
+        checks
+    }
+
+    /// Why the column cannot be stored on `bt`, or `None` if it can.
+    pub fn unsupported_reason(&self, bt: &BackendType) -> Option<&'static str> {
+        match self.type_overrides.get(bt) {
+            "" if !self.serial => self.col_type.unsupported_reason(bt),
+            _ => None,
+        }
+    }
+
+    /// Statement that creates the database type of the column, for
+    /// columns that need their own type (enums on postgres).
+    pub fn create_type_sql(&self, bt: &BackendType) -> Option<String> {
+        match (bt, self.enum_values()) {
+            (BackendType::Postgres, Some((name, values))) => Some(format!(
+                "DO $$ BEGIN\n\tCREATE TYPE {name} AS ENUM ({});\nEXCEPTION\n\tWHEN duplicate_object THEN NULL;\nEND $$;",
+                bt.dialect().quote_list(values)
+            )),
+            _ => None,
+        }
+    }
+
     pub fn to_sql(&self, bt: &BackendType) -> String {
-        use BackendType::*;
         let col = &self.col_name;
 
         if self.serial {
-            match bt {
-                MySql => format!("{col} INT AUTO_INCREMENT PRIMARY KEY"),
-                Postgres => format!("{col} INT GENERATED ALWAYS AS IDENTITY PRIMARY KEY"),
-                Sqlite => format!("{col} INTEGER PRIMARY KEY AUTOINCREMENT"),
-            }
+            bt.dialect().serial_sql(col)
         } else {
-            let col_type = &self.col_type.to_sql(&self.length);
+            let col_type = &match self.is_nullable() {
+                true => self.type_sql(bt),
+                false => format!("{} NOT NULL", self.type_sql(bt)),
+            };
             let unique = if self.unique { "UNIQUE" } else { "" };
             let default_value = &self
-                .default_value
-                .as_ref()
-                .map(|v| format!("DEFAULT {}", v.trim()))
-                .unwrap_or(String::new());
-            format!("{col} {col_type} {unique} {default_value}")
+                .default_sql(bt)
+                .map(|v| format!("DEFAULT {v}"))
+                .unwrap_or_default();
+            let check = &self.checks_sql(bt).join(" ");
+            [col, col_type, unique, default_value, check]
+                .iter()
+                .filter(|part| !part.is_empty())
+                .map(|part| part.to_string())
+                .collect::<Vec<String>>()
+                .join(" ")
         }
     }
 }
 
-impl From<&Field> for FieldDefinition {
-    fn from(value: &Field) -> Self {
+impl FieldDefinition {
+    /// Parses a model field and its `#[modeller(...)]` attributes.
+    /// `table_name` names the types of inline enums and `ctx`
+    /// resolves enums declared alongside the models.
+    pub fn parse(field: &Field, table_name: &str, ctx: &DefinitionContext) -> syn::Result<Self> {
         let Field {
             ident, ty, attrs, ..
-        } = value;
-        let mut col_name = ident
-            .as_ref()
-            .map(|v| v.to_token_stream().to_string())
-            .unwrap_or("".to_string());
+        } = field;
+        let mut col_name = ident.as_ref().map(|v| v.to_string()).unwrap_or_default();
+
+        // `author: ForeignKey<User>` is stored in `author_id`
+        let foreign_key = ColumnType::foreign_key_target(ty, ctx)?;
+        if foreign_key.is_some() {
+            col_name = format!("{col_name}_id");
+        }
 
-        let mut col_type = ty.into();
+        let mut col_type = None;
+        let mut via = None;
+        let mut enum_values: Option<Vec<String>> = None;
+        let mut json = false;
+        let mut check_json = false;
+        let mut check = None;
         let mut serial = false;
+        let mut signed = false;
+        let mut primary_key = false;
         let mut unique = false;
+        let mut indexed = false;
         let mut default_value = None;
-        let mut length = None;
+        let mut default_uuid = false;
+        let mut uuid_storage = None;
+        let mut references = None;
+        let mut belongs_to = None;
+        let mut on_delete = None;
+        let mut on_update = None;
+        let mut size = ColumnSize::default();
+        let mut type_overrides = BackendSql::default();
 
         for attr in attrs {
-            if let Some(ident) = attr.path().get_ident() {
-                if ident == "modeller" {
-                    if let Meta::List(meta) = &attr.meta {
-                        let value = meta.tokens.to_string();
-                        for prop in value.split(",") {
-                            let prop = prop.trim();
-                            if ["serial", "unique"].contains(&prop) {
-                                serial = prop == "serial";
-                                unique = prop == "unique";
-
-                                continue;
-                            }
-
-                            let prop_split: Vec<&str> = prop.split("=").collect();
-                            if let (Some(key), Some(value)) = (prop_split.get(0), prop_split.get(1))
-                            {
-                                let key = key.trim();
-                                if key == "default" {
-                                    default_value = Some(value.to_string())
-                                } else if key == "length" {
-                                    match value.parse::<usize>() {
-                                        Ok(len) => length = Some(len),
-                                        Err(_) => panic!(
-                                            r#"unable to parse attr "length" for field "{col_name}"."#
-                                        ),
-                                    }
-                                } else if key == "name" {
-                                    col_name = value.to_string()
-                                } else if key == "type" {
-                                    col_type = ColumnType::from(*value);
-                                }
-                            }
+            if !attr.path().is_ident("modeller") {
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                let path = &meta.path;
+
+                if path.is_ident("serial") {
+                    serial = true;
+                } else if path.is_ident("primary_key") {
+                    primary_key = true;
+                } else if path.is_ident("signed") {
+                    signed = true;
+                } else if path.is_ident("unique") {
+                    unique = true;
+                } else if path.is_ident("index") {
+                    indexed = true;
+                } else if path.is_ident("default_uuid") {
+                    default_uuid = true;
+                } else if path.is_ident("json") {
+                    json = true;
+                } else if path.is_ident("check_json") {
+                    check_json = true;
+                } else if path.is_ident("check") {
+                    check = Some(attr_sql(&meta)?.0);
+                } else if path.is_ident("default") {
+                    default_value = Some(attr_value(&meta)?);
+                } else if path.is_ident("length") {
+                    size.length = Some(attr_number(&meta, "length", &col_name)?);
+                } else if path.is_ident("precision") {
+                    size.precision = Some(attr_number(&meta, "precision", &col_name)?);
+                } else if path.is_ident("scale") {
+                    size.scale = Some(attr_number(&meta, "scale", &col_name)?);
+                } else if path.is_ident("name") {
+                    col_name = attr_value(&meta)?;
+                } else if path.is_ident("type") {
+                    col_type = Some(match attr_sql(&meta)? {
+                        (sql, true) => ColumnType::Raw(sql),
+                        (name, false) if name == "NULLABLE" => {
+                            return Err(meta.error(format!(
+                                r#"attr "type" for field "{col_name}" requires a type after NULLABLE."#
+                            )));
                         }
-                    }
+                        (name, false) => ColumnType::from(name.as_str()),
+                    });
+                } else if path.is_ident("pg") || path.is_ident("mysql") || path.is_ident("sqlite")
+                {
+                    let sql = if path.is_ident("pg") {
+                        &mut type_overrides.postgres
+                    } else if path.is_ident("mysql") {
+                        &mut type_overrides.mysql
+                    } else {
+                        &mut type_overrides.sqlite
+                    };
+
+                    meta.parse_nested_meta(|meta| {
+                        if meta.path.is_ident("type") {
+                            *sql = attr_sql(&meta)?.0;
+                            Ok(())
+                        } else {
+                            Err(meta.error(format!(
+                                r#"unknown backend attr "{}" for field "{col_name}"."#,
+                                meta.path.to_token_stream()
+                            )))
+                        }
+                    })?;
+                } else if path.is_ident("references") {
+                    let input = meta.value()?;
+                    let table = Ident::parse_any(input)?.to_string();
+                    input.parse::<Token![.]>()?;
+                    let column = Ident::parse_any(input)?.to_string();
+                    references = Some((table, column));
+                } else if path.is_ident("belongs_to") {
+                    belongs_to = Some(attr_value(&meta)?);
+                } else if path.is_ident("on_delete") {
+                    on_delete = Some(attr_action(&meta, "on_delete", &col_name)?);
+                } else if path.is_ident("on_update") {
+                    on_update = Some(attr_action(&meta, "on_update", &col_name)?);
+                } else if path.is_ident("via") {
+                    via = Some(attr_value(&meta)?);
+                } else if path.is_ident("uuid_storage") {
+                    let value = attr_value(&meta)?;
+                    uuid_storage = Some(value.parse().map_err(|_| {
+                        meta.error(format!(
+                            r#"unknown uuid storage "{value}" for field "{col_name}". expected "text" or "binary"."#
+                        ))
+                    })?);
+                } else if path.is_ident("enum") {
+                    let content;
+                    parenthesized!(content in meta.input);
+                    let values = content.parse_terminated(enum_value, Token![,])?;
+                    enum_values = Some(values.into_iter().collect());
+                } else {
+                    return Err(meta.error(format!(
+                        r#"unknown attr "{}" for field "{col_name}"."#,
+                        path.to_token_stream()
+                    )));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        let error = |message: String| syn::Error::new_spanned(field, message);
+
+        let typed_relation = belongs_to.is_some();
+        let relation = match (foreign_key, belongs_to) {
+            (Some(_), Some(_)) => {
+                return Err(error(format!(
+                    r#"attr "belongs_to" cannot be set for "{col_name}", which is a ForeignKey."#
+                )));
+            }
+            (foreign_key, belongs_to) => foreign_key.or(belongs_to),
+        };
+
+        if relation.is_some()
+            && (references.is_some()
+                || col_type.is_some()
+                || enum_values.is_some()
+                || via.is_some()
+                || json)
+        {
+            return Err(error(format!(
+                r#"relation "{col_name}" takes the type of the key it references, and cannot set "references", "type", "enum", "via" or "json"."#
+            )));
+        }
+
+        if via.is_some() && (col_type.is_some() || enum_values.is_some() || json) {
+            return Err(error(format!(
+                r#"attr "via" for field "{col_name}" cannot be combined with "type", "enum" or "json"."#
+            )));
+        }
+
+        let mut col_type = match (col_type, enum_values) {
+            // replaced by the type of the referenced key, see `resolve_references`
+            (None, None) if relation.is_some() && !typed_relation => {
+                ColumnType::Int64.nullable_for(ty, ctx)?
+            }
+            (None, None) if via.is_some() => {
+                let rust_type = via.unwrap_or_default();
+                ColumnType::Custom(CustomType::unresolved(&rust_type)).nullable_for(ty, ctx)?
+            }
+            (Some(_), Some(_)) => {
+                return Err(error(format!(
+                    r#"attrs "type" and "enum" cannot both be set for field "{col_name}"."#
+                )));
+            }
+            // like inferred types, set types are nullable if the field is an `Option`,
+            // or if they are spelled `NULLABLE ...`
+            (Some(col_type), None) => col_type.nullable_for(ty, ctx)?,
+            (None, Some(values)) if values.is_empty() => {
+                return Err(error(format!(
+                    r#"attr "enum" for field "{col_name}" requires at least one value."#
+                )));
+            }
+            (None, Some(values)) => ColumnType::Enum {
+                name: format!("{table_name}_{col_name}"),
+                values,
+            }
+            .nullable_for(ty, ctx)?,
+            (None, None) if json => ColumnType::json(ty, ctx)?,
+            (None, None) => ColumnType::from_type(ty, ctx)?,
+        };
+
+        if let Some(len) = ColumnType::array_len(ty, ctx)?
+            && col_type.is_fixed_binary()
+        {
+            match size.length {
+                Some(length) if length != len => {
+                    return Err(error(format!(
+                        r#"attr "length" for field "{col_name}" does not match its array length {len}."#
+                    )));
+                }
+                _ => size.length = Some(len),
+            }
+        }
+
+        if col_type.is_fixed_binary() && size.length.is_none() {
+            return Err(error(format!(
+                r#"fixed-length binary field "{col_name}" requires attr "length"."#
+            )));
+        }
+
+        if check_json && !col_type.is_json() {
+            return Err(error(format!(
+                r#"attr "check_json" requires "{col_name}" to be a json field."#
+            )));
+        }
+
+        if let Some(storage) = uuid_storage {
+            col_type = match col_type.with_uuid_storage(storage) {
+                Some(col_type) => col_type,
+                None => {
+                    return Err(error(format!(
+                        r#"attr "uuid_storage" requires "{col_name}" to be a uuid field."#
+                    )));
+                }
+            };
+        }
+
+        if signed {
+            col_type = match col_type.signed() {
+                Some(col_type) => col_type,
+                None => {
+                    return Err(error(format!(
+                        r#"attr "signed" requires "{col_name}" to be an unsigned integer field."#
+                    )));
                 }
+            };
+        }
+
+        let default_value = match default_value {
+            // `@uuid` is the portable spelling of `default_uuid`
+            Some(value)
+                if value.replace(' ', "") == "@uuid" && col_type.uuid_storage().is_some() =>
+            {
+                default_uuid = true;
+                None
+            }
+            Some(value) => Some(DefaultValue::parse(&value, &col_type, &size).map_err(
+                |reason| {
+                    error(format!(
+                        r#"attr "default" for field "{col_name}": {reason}"#
+                    ))
+                },
+            )?),
+            None => None,
+        };
+
+        if default_uuid && col_type.uuid_storage().is_none() {
+            return Err(error(format!(
+                r#"attr "default_uuid" requires "{col_name}" to be a uuid field."#
+            )));
+        }
+
+        if let Some(scale) = size.scale
+            && size.precision.is_none_or(|precision| scale > precision)
+        {
+            return Err(error(format!(
+                r#"attr "scale" for field "{col_name}" requires a "precision" of at least {scale}."#
+            )));
+        }
+
+        // the key of relations is inferred from the referenced model
+        let references = references.or(relation.map(|model| (model, String::new())));
+        let references = match references {
+            Some((table, column)) => Some(Reference {
+                table,
+                column,
+                on_delete,
+                on_update,
+            }),
+            None if on_delete.is_some() || on_update.is_some() => {
+                return Err(error(format!(
+                    r#"attrs "on_delete" and "on_update" require "references" for field "{col_name}"."#
+                )));
             }
+            None => None,
+        };
+
+        if !matches!(col_type, ColumnType::Nullable(_))
+            && [on_delete, on_update].contains(&Some(ReferentialAction::SetNull))
+        {
+            return Err(error(format!(
+                r#"action "set_null" requires "{col_name}" to be nullable."#
+            )));
         }
 
-        FieldDefinition {
+        let def = FieldDefinition {
             col_name,
             col_type,
             serial,
+            primary_key,
             unique,
+            indexed,
             default_value,
-            length,
+            default_uuid,
+            check_json,
+            check,
+            references,
+            typed_relation,
+            size,
+            type_overrides,
+        };
+
+        if let Some(bt) = &ctx.bt
+            && let Some(reason) = def.unsupported_reason(bt)
+        {
+            return Err(error(format!(r#"field "{}": {reason}"#, def.col_name)));
+        }
+
+        Ok(def)
+    }
+}
+
+/// Reads the value of a `key = value` attr. The value runs
+/// until the next comma, e.g. `type = NULLABLE TEXT`.
+fn attr_value(meta: &ParseNestedMeta) -> syn::Result<String> {
+    let input = meta.value()?;
+    let mut tokens = TokenStream::new();
+
+    while !input.is_empty() && !input.peek(Token![,]) {
+        tokens.extend([input.parse::<TokenTree>()?]);
+    }
+
+    if tokens.is_empty() {
+        return Err(meta.error("expected a value."));
+    }
+
+    Ok(tokens.to_string())
+}
+
+/// Reads the value of an attr holding SQL, either verbatim from a
+/// string literal (`type = "GEOGRAPHY(POINT)"`) or from its tokens.
+/// Returns whether the value was a string literal.
+fn attr_sql(meta: &ParseNestedMeta) -> syn::Result<(String, bool)> {
+    let value = attr_value(meta)?;
+
+    match syn::parse_str::<LitStr>(&value) {
+        Ok(lit) => Ok((lit.value(), true)),
+        Err(_) => Ok((value, false)),
+    }
+}
+
+fn attr_number<N: FromStr>(meta: &ParseNestedMeta, key: &str, col_name: &str) -> syn::Result<N> {
+    attr_value(meta)?.parse().map_err(|_| {
+        meta.error(format!(
+            r#"unable to parse attr "{key}" for field "{col_name}"."#
+        ))
+    })
+}
+
+fn attr_action(
+    meta: &ParseNestedMeta,
+    key: &str,
+    col_name: &str,
+) -> syn::Result<ReferentialAction> {
+    attr_value(meta)?.parse().map_err(|_| {
+        meta.error(format!(
+            r#"attr "{key}" for field "{col_name}" must be one of cascade, restrict, set_null, set_default or no_action."#
+        ))
+    })
+}
+
+/// A value of an inline `enum(...)` attr, either a name or a string.
+fn enum_value(input: ParseStream) -> syn::Result<String> {
+    if input.peek(LitStr) {
+        Ok(input.parse::<LitStr>()?.value())
+    } else {
+        Ok(Ident::parse_any(input)?.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend_type::BackendType,
+        column::ColumnType,
+        core::{DefinitionContext, DefinitionStream},
+        model::ModelDefinition,
+        test_util::fields_sql,
+    };
+    use quote::quote;
+    use syn::{ItemEnum, ItemStruct, ItemType, parse_quote};
+
+    #[test]
+    fn test_uuid_fields() {
+        let model: ItemStruct = parse_quote! {
+            struct Session {
+                #[modeller(default_uuid)]
+                id: Uuid,
+                #[modeller(uuid_storage=binary)]
+                user_id: Option<uuid::Uuid>
+            }
+        };
+
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::Postgres),
+            vec!["id UUID NOT NULL DEFAULT gen_random_uuid()", "user_id UUID",]
+        );
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::MySql),
+            vec![
+                "id CHAR(36) NOT NULL DEFAULT (UUID())",
+                "user_id BINARY(16)"
+            ]
+        );
+
+        let sqlite = fields_sql(&ModelDefinition::from(&model), &BackendType::Sqlite);
+        assert!(sqlite[0].starts_with("id TEXT NOT NULL DEFAULT (lower(hex(randomblob(4))"));
+        assert_eq!(sqlite[1], "user_id BLOB");
+
+        let model: ItemStruct = parse_quote! {
+            struct Session {
+                #[modeller(uuid_storage=bytes)]
+                id: Uuid
+            }
+        };
+        let err = ModelDefinition::parse(&model, &DefinitionContext::default()).unwrap_err();
+        assert!(
+            err.to_string().contains(r#"unknown uuid storage "bytes""#),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_typed_defaults() {
+        let model: ItemStruct = parse_quote! {
+            struct Event {
+                #[modeller(default = @uuid)]
+                id: Uuid,
+                #[modeller(default = Lagos)]
+                city: String,
+                #[modeller(default = "it's")]
+                note: Option<String>,
+                #[modeller(default = -5)]
+                offset: i8,
+                #[modeller(default = true)]
+                active: bool,
+                #[modeller(default = @now)]
+                created_at: chrono::NaiveDateTime,
+                #[modeller(default = @now)]
+                day: chrono::NaiveDate
+            }
+        };
+
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::Postgres),
+            vec![
+                "id UUID NOT NULL DEFAULT gen_random_uuid()",
+                "city VARCHAR NOT NULL DEFAULT 'Lagos'",
+                "note VARCHAR DEFAULT 'it''s'",
+                "offset SMALLINT NOT NULL DEFAULT -5",
+                "active BOOLEAN NOT NULL DEFAULT TRUE",
+                "created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP",
+                "day DATE NOT NULL DEFAULT CURRENT_DATE"
+            ]
+        );
+        let mysql = fields_sql(&ModelDefinition::from(&model), &BackendType::MySql);
+        assert_eq!(mysql[4], "active TINYINT(1) NOT NULL DEFAULT TRUE");
+        assert_eq!(mysql[6], "day DATE NOT NULL DEFAULT (CURRENT_DATE)");
+        let sqlite = fields_sql(&ModelDefinition::from(&model), &BackendType::Sqlite);
+        assert_eq!(sqlite[4], "active INTEGER NOT NULL DEFAULT 1");
+
+        for invalid in [
+            quote! { #[modeller(default = Lagos)] state: u32 },
+            quote! { #[modeller(default = 300)] level: u8 },
+            quote! { #[modeller(default = yes)] active: bool },
+            quote! { #[modeller(default = @now)] name: String },
+            quote! { #[modeller(default = @today)] day: chrono::NaiveDate },
+            quote! { #[modeller(default = NULL)] name: String },
+            quote! { #[modeller(default = "toolong", length = 4)] code: String },
+        ] {
+            let model: ItemStruct = parse_quote! { struct Event { #invalid } };
+            let err = ModelDefinition::parse(&model, &DefinitionContext::default()).unwrap_err();
+            assert!(err.to_string().contains(r#"attr "default""#), "{err}");
         }
     }
+
+    #[test]
+    fn test_json_fields() {
+        let model: ItemStruct = parse_quote! {
+            struct Account {
+                settings: serde_json::Value,
+                #[modeller(json, check_json)]
+                payload: Option<Payload>
+            }
+        };
+
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::Postgres),
+            vec!["settings JSONB NOT NULL", "payload JSONB"]
+        );
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::MySql),
+            vec!["settings JSON NOT NULL", "payload JSON"]
+        );
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::Sqlite),
+            vec![
+                "settings TEXT NOT NULL",
+                "payload TEXT CHECK (json_valid(payload))"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_binary_fields() {
+        let model: ItemStruct = parse_quote! {
+            struct Upload {
+                hash: [u8; 32],
+                #[modeller(length=1024)]
+                thumbnail: Option<Vec<u8>>,
+                content: Vec<u8>
+            }
+        };
+
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::Postgres),
+            vec![
+                "hash BYTEA NOT NULL",
+                "thumbnail BYTEA",
+                "content BYTEA NOT NULL"
+            ]
+        );
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::MySql),
+            vec![
+                "hash BINARY(32) NOT NULL",
+                "thumbnail VARBINARY(1024)",
+                "content BLOB NOT NULL"
+            ]
+        );
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::Sqlite),
+            vec![
+                "hash BLOB NOT NULL",
+                "thumbnail BLOB",
+                "content BLOB NOT NULL"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enum_fields() {
+        let role: ItemEnum = parse_quote! { enum UserRole { Admin, Member } };
+        let ctx = DefinitionContext {
+            enums: std::slice::from_ref(&role),
+            ..Default::default()
+        };
+        let model: ItemStruct = parse_quote! {
+            struct Member {
+                #[modeller(default=Member)]
+                role: UserRole,
+                #[modeller(enum(draft, "in review"))]
+                status: Option<String>
+            }
+        };
+        let model = ModelDefinition::parse(&model, &ctx).unwrap();
+
+        assert_eq!(
+            fields_sql(&model, &BackendType::Postgres),
+            vec![
+                "role user_role NOT NULL DEFAULT 'Member'",
+                "status member_status"
+            ]
+        );
+        assert_eq!(
+            fields_sql(&model, &BackendType::MySql),
+            vec![
+                "role ENUM('Admin', 'Member') NOT NULL DEFAULT 'Member'",
+                "status ENUM('draft', 'in review')"
+            ]
+        );
+        assert_eq!(
+            fields_sql(&model, &BackendType::Sqlite),
+            vec![
+                "role TEXT NOT NULL DEFAULT 'Member' CHECK (role IN ('Admin', 'Member'))",
+                "status TEXT CHECK (status IN ('draft', 'in review'))"
+            ]
+        );
+
+        let create_sql = model.create_sql("member", &BackendType::Postgres);
+        assert!(
+            create_sql
+                .starts_with("DO $$ BEGIN\n\tCREATE TYPE user_role AS ENUM ('Admin', 'Member');")
+        );
+
+        let tuple: ItemEnum = parse_quote! { enum Shape { Circle(f64) } };
+        assert!(ColumnType::from_enum(&tuple).is_err());
+    }
+
+    #[test]
+    fn test_array_fields() {
+        let model: ItemStruct = parse_quote! {
+            struct Article {
+                tags: Vec<String>,
+                scores: Option<Vec<i64>>,
+                #[modeller(json)]
+                labels: Vec<String>
+            }
+        };
+
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::Postgres),
+            vec![
+                "tags VARCHAR[] NOT NULL",
+                "scores BIGINT[]",
+                "labels JSONB NOT NULL"
+            ]
+        );
+
+        let ctx = DefinitionContext {
+            bt: Some(BackendType::MySql),
+            ..Default::default()
+        };
+        let err = ModelDefinition::parse(&model, &ctx).unwrap_err();
+        assert!(err.to_string().contains("only supported on postgres"));
+
+        let json_only: ItemStruct = parse_quote! {
+            struct Article {
+                #[modeller(json)]
+                tags: Vec<String>
+            }
+        };
+        assert!(ModelDefinition::parse(&json_only, &ctx).is_ok());
+    }
+
+    #[test]
+    fn test_unsigned_fields() {
+        let model: ItemStruct = parse_quote! {
+            struct Counter {
+                #[modeller(default = 18446744073709551615)]
+                hits: u64,
+                level: Option<u8>,
+                #[modeller(signed)]
+                views: u32
+            }
+        };
+
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::Postgres),
+            vec![
+                "hits NUMERIC(20) NOT NULL DEFAULT 18446744073709551615 CHECK (hits >= 0)",
+                "level SMALLINT CHECK (level >= 0)",
+                "views INTEGER NOT NULL"
+            ]
+        );
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::MySql),
+            vec![
+                "hits BIGINT UNSIGNED NOT NULL DEFAULT 18446744073709551615",
+                "level TINYINT UNSIGNED",
+                "views INTEGER NOT NULL"
+            ]
+        );
+
+        // sqlite would round u64 values above i64::MAX
+        let ctx = DefinitionContext {
+            bt: Some(BackendType::Sqlite),
+            ..Default::default()
+        };
+        let err = ModelDefinition::parse(&model, &ctx).unwrap_err();
+        assert!(err.to_string().contains("add #[modeller(signed)]"), "{err}");
+
+        let sqlite: ItemStruct = parse_quote! {
+            struct Counter {
+                #[modeller(serial)]
+                id: u64,
+                #[modeller(signed)]
+                hits: u64,
+                level: Option<u8>
+            }
+        };
+        assert_eq!(
+            fields_sql(
+                &ModelDefinition::parse(&sqlite, &ctx).unwrap(),
+                &BackendType::Sqlite
+            ),
+            vec![
+                "id INTEGER PRIMARY KEY AUTOINCREMENT",
+                "hits BIGINT NOT NULL",
+                "level INTEGER CHECK (level >= 0)"
+            ]
+        );
+
+        let signed: ItemStruct = parse_quote! {
+            struct Counter {
+                #[modeller(signed)]
+                hits: i64
+            }
+        };
+        assert!(ModelDefinition::parse(&signed, &DefinitionContext::default()).is_err());
+    }
+
+    #[test]
+    fn test_structural_types() {
+        let aliases: Vec<ItemType> = vec![
+            parse_quote! { type Tags = Vec<Tag>; },
+            parse_quote! { type Tag = std::string::String; },
+            parse_quote! { type Hash = [u8; 32]; },
+        ];
+        let ctx = DefinitionContext {
+            aliases: &aliases,
+            ..Default::default()
+        };
+        let model: ItemStruct = parse_quote! {
+            struct Post {
+                title: std::string::String,
+                slug: &'static str,
+                summary: Box<str>,
+                published_on: Option< chrono::NaiveDate >,
+                tags: Tags,
+                hash: Option<Hash>
+            }
+        };
+        let model = ModelDefinition::parse(&model, &ctx).unwrap();
+        assert_eq!(
+            fields_sql(&model, &BackendType::Postgres),
+            vec![
+                "title VARCHAR NOT NULL",
+                "slug VARCHAR NOT NULL",
+                "summary VARCHAR NOT NULL",
+                "published_on DATE",
+                "tags VARCHAR[] NOT NULL",
+                "hash BYTEA"
+            ]
+        );
+
+        let recursive: Vec<ItemType> = vec![parse_quote! { type Loop = Loop; }];
+        let ctx = DefinitionContext {
+            aliases: &recursive,
+            ..Default::default()
+        };
+        let model: ItemStruct = parse_quote! { struct Node { next: Loop } };
+        assert!(ModelDefinition::parse(&model, &ctx).is_err());
+
+        let model: ItemStruct = parse_quote! { struct Node { next: HashMap<u8, u8> } };
+        let err = ModelDefinition::parse(&model, &DefinitionContext::default()).unwrap_err();
+        assert!(err.to_string().starts_with("unsupported field type"));
+    }
+
+    #[test]
+    fn test_raw_types() {
+        let model: ItemStruct = parse_quote! {
+            struct Place {
+                #[modeller(type = "GEOGRAPHY(POINT)")]
+                location: Option<Point>,
+                #[modeller(type = "CITEXT", unique)]
+                email: String,
+                #[modeller(pg(type = JSONB), mysql(type = "JSON"), sqlite(type = TEXT))]
+                tags: Vec<String>
+            }
+        };
+
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::Postgres),
+            vec![
+                "location GEOGRAPHY(POINT)",
+                "email CITEXT NOT NULL UNIQUE",
+                "tags JSONB NOT NULL"
+            ]
+        );
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&model), &BackendType::MySql)[2],
+            "tags JSON NOT NULL"
+        );
+
+        // both spellings of a type follow the nullability of the field
+        let nullable: ItemStruct = parse_quote! {
+            struct Place {
+                #[modeller(type = "CITEXT")]
+                email: Option<String>,
+                #[modeller(type = TEXT)]
+                bio: Option<String>,
+                #[modeller(type = NULLABLE TEXT)]
+                notes: String
+            }
+        };
+        assert_eq!(
+            fields_sql(&ModelDefinition::from(&nullable), &BackendType::Postgres),
+            vec!["email CITEXT", "bio TEXT", "notes TEXT"]
+        );
+
+        // arrays are allowed on mysql once they have an override
+        let ctx = DefinitionContext {
+            bt: Some(BackendType::MySql),
+            ..Default::default()
+        };
+        assert!(ModelDefinition::parse(&model, &ctx).is_ok());
+
+        let unknown: ItemStruct = parse_quote! {
+            struct Place {
+                #[modeller(pg(length = 12))]
+                name: String
+            }
+        };
+        assert!(ModelDefinition::parse(&unknown, &DefinitionContext::default()).is_err());
+    }
+
+    #[test]
+    fn test_relation_fields() {
+        let stream: DefinitionStream = parse_quote! {
+            struct User {
+                #[modeller(serial)]
+                id: u64
+            }
+            struct Country {
+                #[modeller(primary_key, length = 2)]
+                code: String
+            }
+            struct Post {
+                #[modeller(serial)]
+                id: u64,
+                #[modeller(on_delete = cascade)]
+                author: ForeignKey<User>,
+                editor: Option<ForeignKey<User>>,
+                #[modeller(belongs_to = Country, unique)]
+                country_code: String
+            }
+        };
+        let models = stream.models().unwrap();
+        let post = &models[2];
+
+        assert_eq!(
+            post.fields()
+                .iter()
+                .map(|field| field.to_sql(&BackendType::MySql))
+                .collect::<Vec<String>>(),
+            vec![
+                "id INT AUTO_INCREMENT PRIMARY KEY",
+                "author_id INTEGER NOT NULL",
+                "editor_id INTEGER",
+                "country_code VARCHAR(2) NOT NULL UNIQUE"
+            ]
+        );
+
+        let reference = post.field("country_code").unwrap().references().unwrap();
+        assert_eq!(
+            (reference.table.as_str(), reference.column.as_str()),
+            ("country", "code")
+        );
+
+        // unique columns are already indexed
+        assert_eq!(
+            post.indexes_sql("post", &BackendType::Postgres),
+            vec![
+                "CREATE INDEX idx_post_author_id ON post (author_id);",
+                "CREATE INDEX idx_post_editor_id ON post (editor_id);"
+            ]
+        );
+
+        // innodb indexes the columns of foreign keys itself
+        assert!(post.indexes_sql("post", &BackendType::MySql).is_empty());
+
+        let keyless: DefinitionStream = parse_quote! {
+            struct User {
+                id: u64
+            }
+            struct Post {
+                author: ForeignKey<User>
+            }
+        };
+        let err = keyless.models().unwrap_err();
+        assert!(err.to_string().contains("single column primary key"));
+
+        let mistyped: DefinitionStream = parse_quote! {
+            struct Country {
+                #[modeller(primary_key, length = 2)]
+                code: String
+            }
+            struct Post {
+                #[modeller(belongs_to = Country)]
+                country_code: u64
+            }
+        };
+        let err = mistyped.models().unwrap_err();
+        assert!(
+            err.to_string().contains(r#"type of "country.code""#),
+            "{err}"
+        );
+    }
 }
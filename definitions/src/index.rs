@@ -0,0 +1,104 @@
+use bincode::{Decode, Encode};
+
+use crate::backend_type::BackendType;
+
+/// An index on columns of a table, created once the table exists.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct Index {
+    pub name: String,
+    /// column names, or expressions in parentheses, e.g. `(lower(email))`.
+    pub columns: Vec<String>,
+    pub unique: bool,
+    /// condition of the rows in a partial index, e.g. `deleted_at IS NULL`.
+    pub predicate: Option<String>,
+    /// whether the index only exists to look up the column of a
+    /// foreign key, which InnoDB already indexes on MySQL.
+    pub foreign_key: bool,
+}
+
+impl Index {
+    pub fn create_sql(&self, table_name: &str) -> String {
+        let unique = if self.unique { "UNIQUE " } else { "" };
+
+        let predicate = match &self.predicate {
+            Some(predicate) => format!(" WHERE {predicate}"),
+            None => String::new(),
+        };
+
+        // parenthesized expressions are also MySQL's functional key parts
+        format!(
+            "CREATE {unique}INDEX {} ON {table_name} ({}){predicate};",
+            self.name,
+            self.columns.join(", ")
+        )
+    }
+
+    /// Whether the database creates the index with the foreign key
+    /// it covers, so it is left out of migrations.
+    pub fn is_implicit(&self, bt: &BackendType) -> bool {
+        self.foreign_key && *bt == BackendType::MySql
+    }
+
+    pub fn unsupported_reason(&self, bt: &BackendType) -> Option<&'static str> {
+        match (bt, &self.predicate) {
+            (BackendType::MySql, Some(_)) => Some("MySQL does not support partial indexes."),
+            _ => None,
+        }
+    }
+
+    /// Index names are scoped to their table on MySQL,
+    /// and to the schema elsewhere.
+    pub fn drop_sql(&self, table_name: &str, bt: &BackendType) -> String {
+        match bt {
+            BackendType::MySql => format!("DROP INDEX {} ON {table_name};", self.name),
+            _ => format!("DROP INDEX IF EXISTS {};", self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend_type::BackendType, core::DefinitionContext, model::ModelDefinition};
+    use syn::{ItemStruct, parse_quote};
+
+    #[test]
+    fn test_partial_and_expression_indexes() {
+        let item: ItemStruct = parse_quote! {
+            #[modeller(
+                index(columns("lower(email)"), unique, where = "deleted_at IS NULL"),
+                index(name = idx_account_domain, columns(tenant, "substr(email, 1, 3)"))
+            )]
+            struct Account {
+                tenant: i64,
+                email: String,
+                deleted_at: Option<chrono::NaiveDateTime>
+            }
+        };
+        let model = ModelDefinition::from(&item);
+
+        assert_eq!(
+            model.indexes_sql("account", &BackendType::Postgres),
+            vec![
+                "CREATE UNIQUE INDEX idx_account_lower_email ON account ((lower(email))) WHERE deleted_at IS NULL;",
+                "CREATE INDEX idx_account_domain ON account (tenant, (substr(email, 1, 3)));"
+            ]
+        );
+        assert!(
+            model.indexes()[0]
+                .unsupported_reason(&BackendType::MySql)
+                .is_some()
+        );
+        assert!(
+            model.indexes()[1]
+                .unsupported_reason(&BackendType::MySql)
+                .is_none()
+        );
+
+        let ctx = DefinitionContext {
+            bt: Some(BackendType::MySql),
+            ..Default::default()
+        };
+        let err = ModelDefinition::parse(&item, &ctx).unwrap_err();
+        assert!(err.to_string().contains("partial indexes"));
+    }
+}
@@ -1,7 +1,16 @@
 pub mod backend_type;
+pub mod check;
 pub mod column;
 pub mod core;
+pub mod custom;
+pub mod default;
+mod dialect;
+pub mod diff;
 pub mod field;
+pub mod index;
 pub mod model;
+pub mod relation;
+#[cfg(test)]
+mod test_util;
 
 pub use bincode;
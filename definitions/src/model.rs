@@ -1,12 +1,20 @@
-use crate::{backend_type::BackendType, field::FieldDefinition};
+use crate::{
+    backend_type::BackendType, check::Check, core::DefinitionContext, field::FieldDefinition,
+    index::Index, relation::ManyToMany,
+};
 use bincode::{Decode, Encode};
 use quote::ToTokens;
-use syn::{Expr, ItemStruct, Meta};
+use syn::{
+    Expr, Ident, ItemStruct, LitStr, Meta, Token, ext::IdentExt, parenthesized, parse::ParseStream,
+};
 
-#[derive(Encode, Decode)]
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
 pub struct ModelDefinition {
     name: String,
     fields: Vec<FieldDefinition>,
+    primary_key: Vec<String>, // excluding serial columns
+    indexes: Vec<Index>,
+    checks: Vec<Check>, // declared on the struct
 }
 
 impl ModelDefinition {
@@ -18,18 +26,214 @@ impl ModelDefinition {
         &self.fields
     }
 
+    pub(crate) fn fields_mut(&mut self) -> &mut [FieldDefinition] {
+        &mut self.fields
+    }
+
+    /// Columns of the primary key, unless it is a serial column.
+    pub fn primary_key(&self) -> &[String] {
+        &self.primary_key
+    }
+
+    pub fn indexes(&self) -> &[Index] {
+        &self.indexes
+    }
+
+    /// `CHECK` constraints of the table, from the `check` attrs of
+    /// its fields and of its struct.
+    pub fn checks(&self) -> Vec<Check> {
+        self.fields
+            .iter()
+            .filter_map(|field| {
+                Some(Check {
+                    name: Check::constraint_name(&self.name, field.col_name()),
+                    expr: field.check()?.to_string(),
+                })
+            })
+            .chain(self.checks.iter().cloned())
+            .collect()
+    }
+
+    pub fn index(&self, name: &str) -> Option<&Index> {
+        self.indexes.iter().find(|index| index.name == name)
+    }
+
+    pub fn field(&self, col_name: &str) -> Option<&FieldDefinition> {
+        self.fields
+            .iter()
+            .find(|field| field.col_name() == col_name)
+    }
+
+    /// Why the model cannot be created on `bt`, or `None` if it can.
+    /// Checked while `parse_models!` expands if the database url is
+    /// set at build time, and by `Modeller::run` otherwise.
+    pub fn unsupported_reason(&self, bt: &BackendType) -> Option<String> {
+        let field = self.fields.iter().find_map(|field| {
+            let reason = field.unsupported_reason(bt)?;
+            Some(format!(r#"field "{}": {reason}"#, field.col_name()))
+        });
+
+        field.or_else(|| {
+            self.indexes.iter().find_map(|index| {
+                let reason = index.unsupported_reason(bt)?;
+                Some(format!(r#"index "{}": {reason}"#, index.name))
+            })
+        })
+    }
+
     pub fn create_table_sql(&self, bt: &BackendType) -> String {
         let table_name = &self.name;
-        let field_sqls: Vec<String> = self
+
+        let mut sqls = vec![
+            format!("DROP TABLE IF EXISTS {table_name};"),
+            self.create_sql(table_name, bt),
+        ];
+        sqls.extend(self.indexes_sql(table_name, bt));
+        sqls.join("\n")
+    }
+
+    /// `CREATE INDEX` statements for the indexes of this model, on
+    /// `table_name`. Run once the table is created.
+    pub(crate) fn indexes_sql(&self, table_name: &str, bt: &BackendType) -> Vec<String> {
+        self.indexes
+            .iter()
+            .filter(|index| !index.is_implicit(bt))
+            .map(|index| index.create_sql(table_name))
+            .collect()
+    }
+
+    /// `CREATE TABLE` statement for this model, using `table_name`
+    /// as the name of the table.
+    pub(crate) fn create_sql(&self, table_name: &str, bt: &BackendType) -> String {
+        self.create_sql_deferring(table_name, &[], bt)
+    }
+
+    /// `CREATE TABLE` statement leaving out the foreign keys of the
+    /// `deferred` columns, which are added once their tables exist.
+    pub(crate) fn create_sql_deferring(
+        &self,
+        table_name: &str,
+        deferred: &[&str],
+        bt: &BackendType,
+    ) -> String {
+        let mut field_sqls: Vec<String> = self
             .fields()
             .iter()
             .map(|field| field.to_sql(bt).trim().to_string())
             .collect();
 
-        format!(
-            "DROP TABLE IF EXISTS {table_name};\nCREATE TABLE {table_name} (\n\t{}\n);",
+        if !self.primary_key.is_empty() {
+            field_sqls.push(format!("PRIMARY KEY ({})", self.primary_key.join(", ")));
+        }
+
+        // foreign keys are named after the model, also when the
+        // table is created under a temporary name to be rebuilt
+        for field in self.fields() {
+            if let Some(reference) = field.references()
+                && !deferred.contains(&field.col_name())
+            {
+                field_sqls.push(reference.constraint_sql(&self.name, field.col_name()));
+            }
+        }
+
+        field_sqls.extend(self.checks().iter().map(Check::constraint_sql));
+
+        // fields can share a type, e.g. the same rust enum
+        let mut sqls: Vec<String> = Vec::new();
+        for sql in self.fields().iter().filter_map(|f| f.create_type_sql(bt)) {
+            if !sqls.contains(&sql) {
+                sqls.push(sql);
+            }
+        }
+
+        sqls.push(format!(
+            "CREATE TABLE {table_name} (\n\t{}\n);",
             field_sqls.join(",\n\t")
-        )
+        ));
+
+        sqls.join("\n")
+    }
+
+    pub fn parse(value: &ItemStruct, ctx: &DefinitionContext) -> syn::Result<Self> {
+        let name = parse_model_name(value);
+        let ItemStruct { fields, .. } = value;
+        let mut fields = fields
+            .iter()
+            .map(|field| FieldDefinition::parse(field, &name, ctx))
+            .collect::<syn::Result<Vec<FieldDefinition>>>()?;
+
+        let primary_key = parse_primary_key(value, &name, &fields)?;
+        for field in &mut fields {
+            let is_key = primary_key.iter().any(|col| col == field.col_name());
+            field.set_primary_key(is_key);
+        }
+
+        let indexes = parse_indexes(value, &name, &fields, &primary_key)?;
+
+        if let Some(bt) = &ctx.bt
+            && let Some((index, reason)) = indexes
+                .iter()
+                .find_map(|index| Some((index, index.unsupported_reason(bt)?)))
+        {
+            return Err(syn::Error::new_spanned(
+                &value.ident,
+                format!(r#"index "{}": {reason}"#, index.name),
+            ));
+        }
+
+        let checks = parse_model_attrs(value)?.checks;
+        let names: Vec<String> = fields
+            .iter()
+            .filter(|field| field.check().is_some())
+            .map(|field| Check::constraint_name(&name, field.col_name()))
+            .chain(checks.iter().map(|check| check.name.clone()))
+            .collect();
+        for (i, check) in names.iter().enumerate() {
+            if names[..i].contains(check) {
+                return Err(syn::Error::new_spanned(
+                    &value.ident,
+                    format!(r#"model "{name}" has two checks named "{check}"."#),
+                ));
+            }
+        }
+
+        Ok(ModelDefinition {
+            name,
+            fields,
+            primary_key,
+            indexes,
+            checks,
+        })
+    }
+}
+
+impl ModelDefinition {
+    /// Join table `name` of a many to many relation, keyed by both
+    /// `(column, table, referenced key)`. Rows are deleted with
+    /// either of the rows they join.
+    pub(crate) fn join_table(name: String, sides: [(String, &str, &FieldDefinition); 2]) -> Self {
+        let fields: Vec<FieldDefinition> = sides
+            .into_iter()
+            .map(|(col, table, key)| FieldDefinition::join_column(col, table, key))
+            .collect();
+        let primary_key: Vec<String> = fields.iter().map(|f| f.col_name().to_string()).collect();
+
+        // the primary key covers lookups by its first column
+        let indexes = vec![Index {
+            name: format!("idx_{name}_{}", primary_key[1]),
+            columns: vec![primary_key[1].clone()],
+            unique: false,
+            predicate: None,
+            foreign_key: true,
+        }];
+
+        ModelDefinition {
+            name,
+            fields,
+            primary_key,
+            indexes,
+            checks: Vec::new(),
+        }
     }
 }
 
@@ -42,13 +246,14 @@ impl ModelDefinition {
 //     }
 // }
 
+/// Shorthand for tests, which panic on invalid models instead of
+/// reporting them. Use [`ModelDefinition::parse`] everywhere else.
+#[cfg(test)]
 impl From<&ItemStruct> for ModelDefinition {
     fn from(value: &ItemStruct) -> Self {
-        let name = parse_model_name(&value);
-        let ItemStruct { fields, .. } = value;
-        ModelDefinition {
-            name,
-            fields: fields.iter().map(FieldDefinition::from).collect(),
+        match ModelDefinition::parse(value, &DefinitionContext::default()) {
+            Ok(def) => def,
+            Err(err) => panic!("{err}"),
         }
     }
 }
@@ -61,37 +266,303 @@ fn parse_model_name(model: &ItemStruct) -> String {
     let mut name = None;
 
     for attr in &model.attrs {
-        if let Some(ident) = attr.path().get_ident() {
-            if ident == "table_name" {
-                if let Meta::NameValue(meta) = &attr.meta {
-                    if let Expr::Lit(syn::ExprLit {
-                        lit: syn::Lit::Str(table_name),
-                        ..
-                    }) = &meta.value
-                    {
-                        name = Some(table_name.value())
+        if let Some(ident) = attr.path().get_ident()
+            && ident == "table_name"
+            && let Meta::NameValue(meta) = &attr.meta
+            && let Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(table_name),
+                ..
+            }) = &meta.value
+        {
+            name = Some(table_name.value())
+        }
+    }
+
+    name.unwrap_or_else(|| snake_case(&model.ident.to_token_stream().to_string()))
+}
+
+/// `#[modeller(...)]` attributes of a model struct.
+#[derive(Default)]
+pub(crate) struct ModelAttrs {
+    pub primary_key: Option<Vec<String>>,
+    pub many_to_many: Vec<ManyToMany>,
+    pub indexes: Vec<Index>, // named once the model is parsed, if unnamed
+    pub checks: Vec<Check>,
+}
+
+pub(crate) fn parse_model_attrs(model: &ItemStruct) -> syn::Result<ModelAttrs> {
+    let mut attrs = ModelAttrs::default();
+
+    for attr in &model.attrs {
+        if !attr.path().is_ident("modeller") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("primary_key") {
+                let content;
+                parenthesized!(content in meta.input);
+                let idents = content.parse_terminated(Ident::parse_any, Token![,])?;
+                attrs.primary_key = Some(idents.iter().map(|ident| ident.to_string()).collect());
+            } else if meta.path.is_ident("index") {
+                let mut index = Index {
+                    name: String::new(),
+                    columns: Vec::new(),
+                    unique: false,
+                    predicate: None,
+                    foreign_key: false,
+                };
+
+                meta.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("name") {
+                        index.name = Ident::parse_any(meta.value()?)?.to_string();
+                    } else if meta.path.is_ident("columns") {
+                        let content;
+                        parenthesized!(content in meta.input);
+                        let columns = content.parse_terminated(index_column, Token![,])?;
+                        index.columns = columns.into_iter().collect();
+                    } else if meta.path.is_ident("unique") {
+                        index.unique = true;
+                    } else if meta.path.is_ident("where") {
+                        index.predicate = Some(meta.value()?.parse::<LitStr>()?.value());
+                    } else {
+                        return Err(meta.error(format!(
+                            r#"unknown index attr "{}" for model "{}"."#,
+                            meta.path.to_token_stream(),
+                            model.ident
+                        )));
+                    }
+
+                    Ok(())
+                })?;
+
+                attrs.indexes.push(index);
+            } else if meta.path.is_ident("check") {
+                let (mut name, mut expr) = (None, None);
+
+                meta.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("name") {
+                        name = Some(Ident::parse_any(meta.value()?)?.to_string());
+                    } else if meta.path.is_ident("expr") {
+                        expr = Some(meta.value()?.parse::<LitStr>()?.value());
+                    } else {
+                        return Err(meta.error(format!(
+                            r#"unknown check attr "{}" for model "{}"."#,
+                            meta.path.to_token_stream(),
+                            model.ident
+                        )));
+                    }
+
+                    Ok(())
+                })?;
+
+                match (name, expr) {
+                    (Some(name), Some(expr)) => attrs.checks.push(Check { name, expr }),
+                    _ => {
+                        return Err(meta.error(format!(
+                            r#"check of model "{}" requires a "name" and an "expr"."#,
+                            model.ident
+                        )));
+                    }
+                }
+            } else if meta.path.is_ident("many_to_many") {
+                let target = Ident::parse_any(meta.value()?)?.to_string();
+                attrs.many_to_many.push(ManyToMany {
+                    target,
+                    through: None,
+                });
+            } else if meta.path.is_ident("through") {
+                // names the join table of the relation before it
+                let through = Ident::parse_any(meta.value()?)?.to_string();
+                match attrs.many_to_many.last_mut() {
+                    Some(relation) if relation.through.is_none() => {
+                        relation.through = Some(through)
                     }
+                    _ => return Err(meta.error(r#"attr "through" must follow "many_to_many"."#)),
                 }
+            } else {
+                return Err(meta.error(format!(
+                    r#"unknown attr "{}" for model "{}"."#,
+                    meta.path.to_token_stream(),
+                    model.ident
+                )));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(attrs)
+}
+
+/// A column of an index, either a name or an expression in a string,
+/// e.g. `columns(tenant, "lower(email)")`.
+fn index_column(input: ParseStream) -> syn::Result<String> {
+    if input.peek(LitStr) {
+        Ok(format!("({})", input.parse::<LitStr>()?.value()))
+    } else {
+        Ok(Ident::parse_any(input)?.to_string())
+    }
+}
+
+/// Parse the indexes of a model: those declared on the struct with
+/// `#[modeller(index(name = idx, columns(a, b), unique))]`, on fields
+/// with `#[modeller(index)]`, and on relations. Unnamed indexes are
+/// named `idx_{table}_{columns}`.
+fn parse_indexes(
+    model: &ItemStruct,
+    name: &str,
+    fields: &[FieldDefinition],
+    primary_key: &[String],
+) -> syn::Result<Vec<Index>> {
+    let error = |message: String| Err(syn::Error::new_spanned(&model.ident, message));
+    let column_index = |col: &str, foreign_key| Index {
+        name: format!("idx_{name}_{col}"),
+        columns: vec![col.to_string()],
+        unique: false,
+        predicate: None,
+        foreign_key,
+    };
+    let mut indexes = Vec::new();
+
+    for field in fields {
+        let col = field.col_name();
+        if field.is_indexed() && (field.is_unique() || field.is_serial()) {
+            return error(format!(
+                r#"field "{col}" of model "{name}" is already indexed by its constraint."#
+            ));
+        }
+
+        // relations are indexed, unless their column already is
+        let is_relation = field.is_unresolved_relation()
+            && !field.is_unique()
+            && primary_key.first().map(String::as_str) != Some(col);
+
+        if field.is_indexed() || is_relation {
+            indexes.push(column_index(col, !field.is_indexed()));
+        }
+    }
+
+    for mut index in parse_model_attrs(model)?.indexes {
+        if index.columns.is_empty() {
+            return error(format!(
+                r#"index of model "{name}" requires at least one column in "columns(...)"."#
+            ));
+        }
+
+        // expressions are checked by the database
+        if let Some(col) = index.columns.iter().find(|col| {
+            !col.starts_with('(') && !fields.iter().any(|field| field.col_name() == col.as_str())
+        }) {
+            return error(format!(
+                r#"index column "{col}" is not a column of model "{name}"."#
+            ));
+        }
+
+        if index.name.is_empty() {
+            let columns: Vec<String> = index
+                .columns
+                .iter()
+                .map(|col| {
+                    col.split(|c: char| !c.is_ascii_alphanumeric())
+                        .filter(|part| !part.is_empty())
+                        .collect::<Vec<&str>>()
+                        .join("_")
+                        .to_lowercase()
+                })
+                .collect();
+            index.name = format!("idx_{name}_{}", columns.join("_"));
+        }
+
+        indexes.push(index);
+    }
+
+    for (i, index) in indexes.iter().enumerate() {
+        if indexes[..i].iter().any(|other| other.name == index.name) {
+            return error(format!(
+                r#"model "{name}" has two indexes named "{}"."#,
+                index.name
+            ));
+        }
+    }
+
+    Ok(indexes)
+}
+
+/// Parse the columns of the primary key of a model, either from a
+/// `#[modeller(primary_key(col_a, col_b))]` attribute on the struct
+/// or from the fields marked `#[modeller(primary_key)]`.
+fn parse_primary_key(
+    model: &ItemStruct,
+    name: &str,
+    fields: &[FieldDefinition],
+) -> syn::Result<Vec<String>> {
+    let columns = parse_model_attrs(model)?.primary_key;
+
+    let error = |message: String| Err(syn::Error::new_spanned(&model.ident, message));
+    let marked: Vec<String> = fields
+        .iter()
+        .filter(|field| field.is_primary_key())
+        .map(|field| field.col_name().to_string())
+        .collect();
+
+    let columns = match columns {
+        Some(_) if !marked.is_empty() => {
+            return error(format!(
+                r#"primary key of model "{name}" is set on both the model and its fields."#
+            ));
+        }
+        Some(columns) => columns,
+        None => marked,
+    };
+
+    if !columns.is_empty() && fields.iter().any(|field| field.is_serial()) {
+        return error(format!(
+            r#"model "{name}" cannot have both a serial column and a primary key."#
+        ));
+    }
+
+    for (i, col) in columns.iter().enumerate() {
+        if columns[..i].contains(col) {
+            return error(format!(
+                r#"column "{col}" appears twice in the primary key of model "{name}"."#
+            ));
+        }
+
+        match fields.iter().find(|field| field.col_name() == col) {
+            None => {
+                return error(format!(
+                    r#"primary key column "{col}" is not a column of model "{name}"."#
+                ));
             }
+            Some(field) if field.is_nullable() => {
+                return error(format!(
+                    r#"primary key column "{col}" of model "{name}" cannot be nullable."#
+                ));
+            }
+            _ => {}
         }
     }
 
-    name.unwrap_or_else(|| {
-        let struct_name = model.ident.to_token_stream().to_string();
-        let mut name = String::new();
+    Ok(columns)
+}
 
-        for (i, c) in struct_name.chars().enumerate() {
-            if c.is_uppercase() {
-                if i > 0 {
-                    name.push('_');
-                }
+/// Converts a Rust type name to a snake_case database name,
+/// e.g. `AnotherModel` to `another_model`.
+pub(crate) fn snake_case(type_name: &str) -> String {
+    let mut name = String::new();
 
-                name.push(c.to_ascii_lowercase());
-            } else {
-                name.push(c);
+    for (i, c) in type_name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                name.push('_');
             }
+
+            name.push(c.to_ascii_lowercase());
+        } else {
+            name.push(c);
         }
+    }
 
-        name
-    })
+    name
 }
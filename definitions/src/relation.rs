@@ -0,0 +1,390 @@
+use bincode::{Decode, Encode};
+use std::str::FromStr;
+use syn::ItemStruct;
+
+use crate::{
+    field::FieldDefinition,
+    model::{ModelDefinition, parse_model_attrs},
+};
+
+/// What the database does to referencing rows when the
+/// referenced row is deleted or updated.
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode)]
+pub enum ReferentialAction {
+    Cascade,
+    Restrict,
+    SetNull,
+    SetDefault,
+    NoAction,
+}
+
+impl ReferentialAction {
+    pub fn sql(&self) -> &'static str {
+        match self {
+            ReferentialAction::Cascade => "CASCADE",
+            ReferentialAction::Restrict => "RESTRICT",
+            ReferentialAction::SetNull => "SET NULL",
+            ReferentialAction::SetDefault => "SET DEFAULT",
+            ReferentialAction::NoAction => "NO ACTION",
+        }
+    }
+}
+
+impl FromStr for ReferentialAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cascade" => Ok(ReferentialAction::Cascade),
+            "restrict" => Ok(ReferentialAction::Restrict),
+            "set_null" => Ok(ReferentialAction::SetNull),
+            "set_default" => Ok(ReferentialAction::SetDefault),
+            "no_action" => Ok(ReferentialAction::NoAction),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Target of a foreign key column, declared with
+/// `#[modeller(references = user.id, on_delete = cascade)]`.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct Reference {
+    /// table name of the referenced model, once resolved
+    /// by `resolve_references`.
+    pub table: String,
+    pub column: String,
+    pub on_delete: Option<ReferentialAction>,
+    pub on_update: Option<ReferentialAction>,
+}
+
+impl Reference {
+    /// Foreign keys are named `fk_{table}_{col}`, so migrations can
+    /// drop them on every backend.
+    pub fn constraint_name(table_name: &str, col: &str) -> String {
+        format!("fk_{table_name}_{col}")
+    }
+
+    /// `FOREIGN KEY` table constraint for the column `col` of `table_name`.
+    pub fn constraint_sql(&self, table_name: &str, col: &str) -> String {
+        let mut sql = format!(
+            "CONSTRAINT {} FOREIGN KEY ({col}) REFERENCES {} ({})",
+            Reference::constraint_name(table_name, col),
+            self.table,
+            self.column
+        );
+
+        if let Some(action) = self.on_delete {
+            sql.push_str(&format!(" ON DELETE {}", action.sql()));
+        }
+
+        if let Some(action) = self.on_update {
+            sql.push_str(&format!(" ON UPDATE {}", action.sql()));
+        }
+
+        sql
+    }
+}
+
+/// A `#[modeller(many_to_many = Tag, through = post_tags)]` relation,
+/// stored in a join table.
+pub(crate) struct ManyToMany {
+    pub target: String,
+    pub through: Option<String>,
+}
+
+/// The model named `name` in `models`, parsed from `items`. Models
+/// can be referenced by struct or table name.
+fn find_model<'a>(
+    name: &str,
+    models: &'a [ModelDefinition],
+    items: &[ItemStruct],
+) -> Option<&'a ModelDefinition> {
+    items
+        .iter()
+        .position(|item| item.ident == name)
+        .or_else(|| models.iter().position(|m| m.name() == name))
+        .map(|i| &models[i])
+}
+
+/// The single column primary key of `model`, if any.
+fn single_key(model: &ModelDefinition) -> Option<&FieldDefinition> {
+    model
+        .fields()
+        .iter()
+        .find(|field| field.is_serial() || model.primary_key() == [field.col_name()])
+}
+
+/// Join tables of the many to many relations of `models`, parsed from
+/// `items`. Join tables are named `{model}_{target}` unless `through`
+/// names them, and have a column referencing the key of each side.
+/// A relation is declared on one of its models only, since declaring
+/// it on both would create two join tables.
+pub(crate) fn join_tables(
+    models: &[ModelDefinition],
+    items: &[ItemStruct],
+) -> syn::Result<Vec<ModelDefinition>> {
+    let mut tables = Vec::new();
+    let mut pairs: Vec<(&str, &str)> = Vec::new();
+
+    for (item, model) in items.iter().zip(models) {
+        for relation in parse_model_attrs(item)?.many_to_many {
+            let error = |message: String| Err(syn::Error::new_spanned(&item.ident, message));
+            let Some(target) = find_model(&relation.target, models, items) else {
+                return error(format!(
+                    r#"many_to_many relation of model "{}" references unknown model "{}"."#,
+                    model.name(),
+                    relation.target
+                ));
+            };
+
+            let (Some(key), Some(target_key)) = (single_key(model), single_key(target)) else {
+                return error(format!(
+                    r#"many_to_many relation between "{}" and "{}" requires both models to have a single column primary key."#,
+                    model.name(),
+                    target.name()
+                ));
+            };
+
+            if model.name() != target.name() && pairs.contains(&(target.name(), model.name())) {
+                return error(format!(
+                    r#"many_to_many relation between "{}" and "{}" is declared on both models. declare it on one of them."#,
+                    target.name(),
+                    model.name()
+                ));
+            }
+            pairs.push((model.name(), target.name()));
+
+            let col = format!("{}_id", model.name());
+            let mut target_col = format!("{}_id", target.name());
+            if target_col == col {
+                target_col = format!("related_{target_col}");
+            }
+
+            let name = relation
+                .through
+                .unwrap_or_else(|| format!("{}_{}", model.name(), target.name()));
+            tables.push(ModelDefinition::join_table(
+                name,
+                [
+                    (col, model.name(), key),
+                    (target_col, target.name(), target_key),
+                ],
+            ));
+        }
+    }
+
+    Ok(tables)
+}
+
+/// Checks the foreign keys of `models`, parsed from `items`, against
+/// the models they reference. Models can be referenced by struct or
+/// table name, which the references are resolved to. Relations
+/// reference the primary key of their model, and take its type.
+pub(crate) fn resolve_references(
+    models: &mut [ModelDefinition],
+    items: &[ItemStruct],
+) -> syn::Result<()> {
+    for i in 0..models.len() {
+        for j in 0..models[i].fields().len() {
+            let field = &models[i].fields()[j];
+            let Some(reference) = field.references() else {
+                continue;
+            };
+
+            let col = field.col_name();
+            let error = |message: String| Err(syn::Error::new_spanned(&items[i].ident, message));
+            let Some(target) = find_model(&reference.table, models, items) else {
+                return error(format!(
+                    r#"field "{col}" references unknown model "{}"."#,
+                    reference.table
+                ));
+            };
+
+            if field.is_unresolved_relation() {
+                let Some(key) = single_key(target) else {
+                    return error(format!(
+                        r#"relation "{col}" requires model "{}" to have a single column primary key."#,
+                        target.name()
+                    ));
+                };
+
+                if field.is_typed_relation() && !field.matches_key(key) {
+                    return error(format!(
+                        r#"field "{col}" does not have the type of "{}.{}", which it references."#,
+                        target.name(),
+                        key.col_name()
+                    ));
+                }
+
+                let (table, key) = (target.name().to_string(), key.clone());
+                models[i].fields_mut()[j].resolve_relation(table, &key);
+                continue;
+            }
+
+            let Some(target_field) = target.field(&reference.column) else {
+                return error(format!(
+                    r#"field "{col}" references unknown column "{}" of model "{}"."#,
+                    reference.column,
+                    target.name()
+                ));
+            };
+
+            // databases only accept keys that identify a single row
+            if !target_field.is_serial()
+                && !target_field.is_unique()
+                && target.primary_key() != [reference.column.as_str()]
+            {
+                return error(format!(
+                    r#"field "{col}" references "{}.{}", which is neither a primary key nor unique."#,
+                    target.name(),
+                    reference.column
+                ));
+            }
+
+            // databases reject or silently convert keys of another type
+            if !field.matches_key(target_field) {
+                return error(format!(
+                    r#"field "{col}" does not have the type of "{}.{}", which it references."#,
+                    target.name(),
+                    reference.column
+                ));
+            }
+
+            let table = target.name().to_string();
+            models[i].fields_mut()[j].resolve_reference(table);
+        }
+    }
+
+    Ok(())
+}
+
+/// Orders `models` so every model comes after the models it references,
+/// and can be created once they exist. References closing a cycle cannot
+/// be created with their table, and are returned as `(model, col)` to be
+/// added once every table exists. Tables referencing themselves are not
+/// a cycle.
+pub(crate) fn dependency_order<'a>(
+    models: &[&'a ModelDefinition],
+) -> (
+    Vec<&'a ModelDefinition>,
+    Vec<(&'a ModelDefinition, &'a str)>,
+) {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        Visiting,
+        Done,
+    }
+
+    fn visit<'a>(
+        i: usize,
+        models: &[&'a ModelDefinition],
+        states: &mut [State],
+        order: &mut Vec<&'a ModelDefinition>,
+        deferred: &mut Vec<(&'a ModelDefinition, &'a str)>,
+    ) {
+        states[i] = State::Visiting;
+
+        for field in models[i].fields() {
+            // references to tables outside `models` already exist
+            let Some(j) = field
+                .references()
+                .and_then(|reference| models.iter().position(|m| m.name() == reference.table))
+            else {
+                continue;
+            };
+
+            match states[j] {
+                _ if i == j => {}
+                State::Unvisited => visit(j, models, states, order, deferred),
+                State::Visiting => deferred.push((models[i], field.col_name())),
+                State::Done => {}
+            }
+        }
+
+        states[i] = State::Done;
+        order.push(models[i]);
+    }
+
+    let mut states = vec![State::Unvisited; models.len()];
+    let mut order = Vec::new();
+    let mut deferred = Vec::new();
+
+    for i in 0..models.len() {
+        if states[i] == State::Unvisited {
+            visit(i, models, &mut states, &mut order, &mut deferred);
+        }
+    }
+
+    (order, deferred)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend_type::BackendType, core::DefinitionStream, diff::create_models};
+    use syn::parse_quote;
+
+    #[test]
+    fn test_join_tables() {
+        let stream: DefinitionStream = parse_quote! {
+            #[modeller(many_to_many = Tag)]
+            struct Post {
+                #[modeller(serial)]
+                id: u64
+            }
+            #[modeller(many_to_many = User, through = friendship)]
+            struct User {
+                #[modeller(primary_key)]
+                id: uuid::Uuid
+            }
+            struct Tag {
+                #[modeller(primary_key, length = 32)]
+                name: String
+            }
+        };
+        let models = stream.models().unwrap();
+        let names: Vec<&str> = models.iter().map(|model| model.name()).collect();
+        assert_eq!(names, vec!["post", "user", "tag", "post_tag", "friendship"]);
+
+        let models: Vec<_> = models.iter().collect();
+        let sql = create_models(&models)
+            .iter()
+            .map(|change| change.to_sql(&BackendType::Postgres))
+            .collect::<Vec<String>>();
+        assert_eq!(
+            sql[3],
+            "CREATE TABLE post_tag (\n\tpost_id INTEGER NOT NULL,\n\ttag_id VARCHAR(32) NOT NULL,\n\tPRIMARY KEY (post_id, tag_id),\n\tCONSTRAINT fk_post_tag_post_id FOREIGN KEY (post_id) REFERENCES post (id) ON DELETE CASCADE,\n\tCONSTRAINT fk_post_tag_tag_id FOREIGN KEY (tag_id) REFERENCES tag (name) ON DELETE CASCADE\n);\nCREATE INDEX idx_post_tag_tag_id ON post_tag (tag_id);"
+        );
+        assert!(sql[4].starts_with(
+            "CREATE TABLE friendship (\n\tuser_id UUID NOT NULL,\n\trelated_user_id UUID NOT NULL,"
+        ));
+
+        let clash: DefinitionStream = parse_quote! {
+            #[modeller(many_to_many = Tag, through = tag)]
+            struct Post {
+                #[modeller(serial)]
+                id: u64
+            }
+            struct Tag {
+                #[modeller(serial)]
+                id: u64
+            }
+        };
+        assert!(clash.models().is_err());
+
+        let both_sides: DefinitionStream = parse_quote! {
+            #[modeller(many_to_many = Tag)]
+            struct Post {
+                #[modeller(serial)]
+                id: u64
+            }
+            #[modeller(many_to_many = Post)]
+            struct Tag {
+                #[modeller(serial)]
+                id: u64
+            }
+        };
+        let err = both_sides.models().unwrap_err();
+        assert!(err.to_string().contains("declared on both models"), "{err}");
+    }
+}
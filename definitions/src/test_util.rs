@@ -0,0 +1,11 @@
+use crate::{backend_type::BackendType, model::ModelDefinition};
+
+/// The column definitions of `model` on `bt`, as they appear in
+/// its `CREATE TABLE` statement.
+pub(crate) fn fields_sql(model: &ModelDefinition, bt: &BackendType) -> Vec<String> {
+    model
+        .fields()
+        .iter()
+        .map(|field| field.to_sql(bt).trim().to_string())
+        .collect()
+}
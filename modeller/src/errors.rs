@@ -1,5 +1,7 @@
 use std::{env, fmt::Display, io};
 
+use crate::{SCHEMAS_DIR, metadata::METADATA_VERSION};
+
 pub type OpResult<T> = Result<T, Error>;
 
 #[derive(Debug)]
@@ -9,6 +11,24 @@ pub enum Error {
     DBError(rbatis::Error),
     ParseError(String),
     InternalError(String),
+    ModelError(String),
+    MigrationError {
+        migration: String,
+        index: usize,
+        statement: String,
+        error: rbatis::Error,
+    },
+    BranchedHistory {
+        parent: String,
+        schema: String,
+        migrations: (String, String),
+    },
+    /// The metadata file was written by another version of modeller,
+    /// or before metadata was versioned when `version` is `None`.
+    OutdatedMetadata {
+        path: String,
+        version: Option<u32>,
+    },
 }
 
 impl Display for Error {
@@ -21,6 +41,45 @@ impl Display for Error {
             DBError(err) => write!(f, "{err}"),
             ParseError(msg) => write!(f, "{msg}"),
             InternalError(msg) => write!(f, "{msg}"),
+            ModelError(msg) => write!(f, "{msg}"),
+            MigrationError {
+                migration,
+                index,
+                statement,
+                error,
+            } => write!(
+                f,
+                "migration {migration} failed at statement #{index}: {error}\n{statement}"
+            ),
+            BranchedHistory {
+                parent,
+                schema,
+                migrations: (first, second),
+            } => write!(
+                f,
+                "migrations {first} and {second} were both generated from schema {parent}, \
+                most likely on parallel branches, and cannot be applied one after the other. \
+                either regenerate {second}: delete it and run modeller again, which restores the \
+                metadata of schema {schema} (written with {first}) from the \"{SCHEMAS_DIR}\" \
+                folder of the migrations directory and generates a new migration for the remaining \
+                changes; or, if both changes are independent, merge them by setting the parent of \
+                {second} to \"-- modeller:parent {schema}\"."
+            ),
+            OutdatedMetadata { path, version } => {
+                let written_by = match version {
+                    Some(version) => format!("metadata version {version}"),
+                    None => "a version of modeller without metadata versions".to_string(),
+                };
+                write!(
+                    f,
+                    "the metadata file {path} was written by {written_by} and cannot be read \
+                    by this version (metadata version {METADATA_VERSION}). to regenerate it: \
+                    apply every migration with the modeller version that wrote them, make sure \
+                    the models match the schema of the latest migration, then delete {path} and \
+                    run modeller again. modeller records the current models as the new metadata \
+                    without writing a migration."
+                )
+            }
         }
     }
 }
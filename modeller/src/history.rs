@@ -0,0 +1,144 @@
+use crate::errors::Error;
+
+const PARENT_KEY: &str = "-- modeller:parent";
+const SCHEMA_KEY: &str = "-- modeller:schema";
+
+/// Hashes encoded model definitions (FNV-1a, 64 bit). The hash is
+/// stable across builds and platforms, so it can be stored in files.
+pub(crate) fn schema_hash(raw: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for byte in raw {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    format!("{hash:016x}")
+}
+
+/// Header written at the top of every migration. `parent` is the
+/// metadata the migration was generated from and `schema` the metadata
+/// after it.
+pub(crate) fn migration_header(parent: &[u8], schema: &[u8]) -> String {
+    format!(
+        "{PARENT_KEY} {}\n{SCHEMA_KEY} {}\n\n",
+        schema_hash(parent),
+        schema_hash(schema)
+    )
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct MigrationHeader {
+    pub parent: String,
+    pub schema: String,
+}
+
+/// Reads the header of a migration. Returns `None` for migrations
+/// written before headers were introduced.
+pub(crate) fn parse_header(content: &str) -> Option<MigrationHeader> {
+    let mut parent = None;
+    let mut schema = None;
+
+    for line in content.lines().take_while(|line| line.starts_with("--")) {
+        if let Some(hash) = line.strip_prefix(PARENT_KEY) {
+            parent = Some(hash.trim().to_string());
+        } else if let Some(hash) = line.strip_prefix(SCHEMA_KEY) {
+            schema = Some(hash.trim().to_string());
+        }
+    }
+
+    Some(MigrationHeader {
+        parent: parent?,
+        schema: schema?,
+    })
+}
+
+/// Checks that no two migrations were generated from the same schema,
+/// which happens when migrations are generated on parallel branches and
+/// merged. `migrations` holds the filename and header of each migration,
+/// in the order they were generated.
+pub(crate) fn check_branches(migrations: &[(String, MigrationHeader)]) -> Result<(), Error> {
+    for (i, (filename, header)) in migrations.iter().enumerate() {
+        let sibling = migrations[i + 1..]
+            .iter()
+            .find(|(_, other)| other.parent == header.parent);
+
+        if let Some((other, _)) = sibling {
+            return Err(Error::BranchedHistory {
+                parent: header.parent.clone(),
+                schema: header.schema.clone(),
+                migrations: (filename.clone(), other.clone()),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// The schema whose metadata should replace the metadata hashed
+/// `current`, if any. Metadata is recorded for the schema of every
+/// generated migration (see `has_snapshot`). When the migration the
+/// current metadata was written with has been deleted, e.g. to
+/// regenerate one of two branched migrations, the metadata of the
+/// latest remaining migration must be restored before diffing.
+pub(crate) fn restore_target<'a>(
+    migrations: &'a [(String, MigrationHeader)],
+    current: &str,
+    has_snapshot: impl Fn(&str) -> bool,
+) -> Option<&'a str> {
+    let deleted = has_snapshot(current)
+        && !migrations
+            .iter()
+            .any(|(_, header)| header.schema == current);
+    let (_, latest) = migrations.last()?;
+
+    (deleted && has_snapshot(&latest.schema)).then_some(latest.schema.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_branches, migration_header, parse_header, restore_target, schema_hash};
+
+    #[test]
+    fn test_check_branches() {
+        let header = migration_header(b"", b"first");
+        let first = parse_header(&format!("{header}CREATE TABLE a (id INTEGER);")).unwrap();
+        assert_eq!(first.parent, schema_hash(b""));
+        assert_eq!(first.schema, schema_hash(b"first"));
+
+        let second = parse_header(&migration_header(b"first", b"second")).unwrap();
+        let branched = parse_header(&migration_header(b"first", b"other")).unwrap();
+
+        let mut migrations = vec![("a.sql".to_string(), first), ("b.sql".to_string(), second)];
+        assert!(check_branches(&migrations).is_ok());
+
+        migrations.push(("c.sql".to_string(), branched));
+        assert!(check_branches(&migrations).is_err());
+        assert!(parse_header("CREATE TABLE a (id INTEGER);").is_none());
+    }
+
+    #[test]
+    fn test_restore_target() {
+        let first = parse_header(&migration_header(b"", b"first")).unwrap();
+        let second = parse_header(&migration_header(b"first", b"second")).unwrap();
+        let mut migrations = vec![("a.sql".to_string(), first), ("b.sql".to_string(), second)];
+        let snapshots = [schema_hash(b"first"), schema_hash(b"second")];
+        let has_snapshot = |hash: &str| snapshots.iter().any(|snapshot| snapshot == hash);
+
+        assert_eq!(
+            restore_target(&migrations, &schema_hash(b"second"), has_snapshot),
+            None
+        );
+        // metadata updated without a migration is kept
+        assert_eq!(
+            restore_target(&migrations, &schema_hash(b"third"), has_snapshot),
+            None
+        );
+
+        migrations.pop();
+        assert_eq!(
+            restore_target(&migrations, &schema_hash(b"second"), has_snapshot),
+            Some(schema_hash(b"first").as_str())
+        );
+    }
+}
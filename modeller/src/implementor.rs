@@ -4,27 +4,48 @@ use std::path::{Path, PathBuf};
 
 use crate::{
     DB_URL_KEY, DEFAULT_DB, DEFAULT_MIG_DIR, METADATA_FILENAME, MIG_DIR_KEY, MIG_TABLE_NAME,
+    SCHEMAS_DIR,
     errors::{Error, OpResult},
-    generate_migration_filename, open_file,
+    generate_migration_filename,
+    history::{
+        MigrationHeader, check_branches, migration_header, parse_header, restore_target,
+        schema_hash,
+    },
+    is_migration_file,
+    metadata::{decode_metadata, encode_metadata},
+    migration_order, migration_version, open_file,
+    splitter::split_statements,
 };
-use definitions::{backend_type::BackendType, model::ModelDefinition};
-use rbatis::RBatis;
+use definitions::{
+    backend_type::BackendType,
+    diff::{SchemaChange, create_models, describe_changes, diff_models},
+    model::ModelDefinition,
+};
+use rbatis::{RBatis, executor::Executor};
 use rbdc_mysql::MysqlDriver;
 use rbdc_pg::PgDriver;
 use rbdc_sqlite::SqliteDriver;
 use tokio::io::AsyncWriteExt;
 
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub filename: String,
+    pub applied: bool,
+}
+
 pub struct Modeller<'a> {
     bt: BackendType,
     db_url: String,
     db_pool: RBatis,
     migrations_dir: String,
+    migration_name: Option<String>,
     raw: &'a [u8],
 }
 
 impl<'a> Modeller<'a> {
     /// run Modeller instance
     pub async fn run(&self) -> OpResult<()> {
+        self.check_models()?;
         self.connect().await?;
 
         let dir = Path::new(&self.migrations_dir);
@@ -33,21 +54,62 @@ impl<'a> Modeller<'a> {
         if !dir_exists {
             self.init().await?;
             self.write_first_migration().await?;
+        } else if !self.migrations_path().join(METADATA_FILENAME).exists() {
+            // the metadata was deleted to be regenerated, see `Error::OutdatedMetadata`
+            self.update_metadata().await?;
+            println!(
+                "modeller: recorded the current models in {}. no migration was written.",
+                self.metadata_filename()?
+            );
         } else {
+            self.check_history().await?;
+            self.restore_metadata().await?;
             let metadata = self.load_metadata().await?;
-            let raw = self.raw;
 
-            if &metadata == raw {
+            if metadata == self.raw {
                 println!("modeller: no changes detected!")
+            } else {
+                self.write_changes_migration(&metadata).await?;
             }
         }
 
         self.run_pending_migrations().await?;
-        self.update_metadata().await?;
 
         Ok(())
     }
 
+    /// get the status of every migration in the migrations directory,
+    /// in the order they are applied.
+    pub async fn status(&self) -> OpResult<Vec<MigrationStatus>> {
+        if !self.migrations_path().is_dir() {
+            return Ok(vec![]);
+        }
+
+        self.connect().await?;
+        self.check_history().await?;
+
+        let pvs = self.previous_migrations().await?;
+        let statuses = self
+            .migration_files()
+            .await?
+            .iter()
+            .map(|file| {
+                let filename = file.display().to_string();
+                let applied = pvs.contains(&filename);
+                MigrationStatus { filename, applied }
+            })
+            .collect();
+
+        Ok(statuses)
+    }
+
+    /// use `name` for the next generated migration instead of a
+    /// name derived from its schema changes.
+    pub fn with_migration_name(mut self, name: &str) -> Self {
+        self.migration_name = Some(name.to_string());
+        self
+    }
+
     /// initializes modeller.
     /// - attempts to connect to the database
     /// - create database "migrations" table if it doesn't exist
@@ -70,7 +132,10 @@ impl<'a> Modeller<'a> {
             );"
         );
 
-        self.db_pool.exec(&query, vec![]).await?;
+        for statement in split_statements(&query, &self.bt) {
+            self.db_pool.exec(&statement, vec![]).await?;
+        }
+
         Ok(())
     }
 
@@ -101,30 +166,143 @@ impl<'a> Modeller<'a> {
     }
 
     async fn write_first_migration(&self) -> OpResult<()> {
-        let models = self.models();
-        let create_sqls: Vec<String> = models
+        let models = self.models()?;
+        let models: Vec<&ModelDefinition> = models.iter().collect();
+        let changes = create_models(&models);
+
+        // tables are dropped before the tables they reference
+        let mut sqls: Vec<String> = changes
+            .iter()
+            .rev()
+            .filter_map(|change| match change {
+                SchemaChange::CreateTable { model, .. } => {
+                    Some(format!("DROP TABLE IF EXISTS {};", model.name()))
+                }
+                _ => None,
+            })
+            .collect();
+        sqls.extend(
+            changes
+                .iter()
+                .map(|change| change.to_sql(&self.bt))
+                .filter(|sql| !sql.is_empty()),
+        );
+        let name = describe_changes(&changes);
+
+        self.write_migration(&name, &sqls.join("\n\n")).await
+    }
+
+    /// writes a migration for the changes between the models recorded
+    /// in `metadata` and the current models.
+    async fn write_changes_migration(&self, metadata: &[u8]) -> OpResult<()> {
+        let previous = decode_models(metadata)?;
+        let models = self.models()?;
+        let changes = diff_models(&previous, &models);
+
+        if changes.is_empty() {
+            println!("modeller: no changes detected!");
+            return self.update_metadata().await;
+        }
+
+        let name = describe_changes(&changes);
+        let sqls: Vec<String> = changes
             .iter()
-            .map(|model| model.create_table_sql(&self.bt))
+            .map(|change| change.to_sql(&self.bt))
+            .filter(|sql| !sql.is_empty())
             .collect();
 
-        // create migration file
-        let mut filename = generate_migration_filename();
+        self.write_migration(&name, &sqls.join("\n\n")).await
+    }
+
+    /// creates a new migration file with `content` and records the
+    /// current models as the metadata the next migration is diffed against.
+    async fn write_migration(&self, name: &str, content: &str) -> OpResult<()> {
+        let name = self.migration_name.as_deref().unwrap_or(name);
+        let last_version = self
+            .migration_files()
+            .await?
+            .iter()
+            .filter_map(|path| path.file_name()?.to_str().and_then(migration_version))
+            .max();
+
+        let mut filename = generate_migration_filename(name, last_version);
         filename = self.build_mig_path(&filename)?;
 
-        let mut file = open_file(&filename).await?;
-        let content = create_sqls.join("\n\n");
+        let parent = self.load_metadata().await?;
+        let header = migration_header(&parent, self.raw);
 
+        let mut file = open_file(&filename).await?;
+        file.write_all(header.as_bytes()).await?;
         file.write_all(content.as_bytes()).await?;
 
+        // kept to restore the metadata if a later migration is deleted
+        let schemas = self.migrations_path().join(SCHEMAS_DIR);
+        tokio::fs::create_dir_all(&schemas).await?;
+        tokio::fs::write(
+            schemas.join(schema_hash(self.raw)),
+            encode_metadata(self.raw),
+        )
+        .await?;
+
+        self.update_metadata().await
+    }
+
+    /// restores the metadata of the latest migration when the migration
+    /// the metadata was written with has been deleted, see `restore_target`.
+    async fn restore_metadata(&self) -> OpResult<()> {
+        let current = schema_hash(&self.load_metadata().await?);
+        let headers = self.migration_headers().await?;
+        let schemas = self.migrations_path().join(SCHEMAS_DIR);
+
+        if let Some(schema) =
+            restore_target(&headers, &current, |hash| schemas.join(hash).is_file())
+        {
+            tokio::fs::copy(schemas.join(schema), self.metadata_filename()?).await?;
+            println!("modeller: restored the metadata of schema {schema}.");
+        }
+
         Ok(())
     }
 
-    fn models(&self) -> Vec<ModelDefinition> {
-        let config = config::standard();
-        match bincode::decode_from_slice(&self.raw, config) {
-            Ok((encoded, _)) => encoded,
-            Err(_) => vec![],
+    /// returns an error if two migrations were generated from the same
+    /// schema, e.g. on parallel branches that were later merged.
+    async fn check_history(&self) -> OpResult<()> {
+        check_branches(&self.migration_headers().await?)
+    }
+
+    /// the filename and header of every migration with a header,
+    /// in the order they are applied.
+    async fn migration_headers(&self) -> OpResult<Vec<(String, MigrationHeader)>> {
+        let mut headers = Vec::new();
+
+        for path in self.migration_files().await? {
+            let content = tokio::fs::read_to_string(&path).await?;
+            if let Some(header) = parse_header(&content) {
+                headers.push((path.display().to_string(), header));
+            }
+        }
+
+        Ok(headers)
+    }
+
+    /// returns an error if a model uses a column type or index the
+    /// backend does not support. models are only checked at compile
+    /// time when the database url is set while building.
+    fn check_models(&self) -> OpResult<()> {
+        for model in self.models()? {
+            if let Some(reason) = model.unsupported_reason(&self.bt) {
+                return Err(Error::ModelError(format!(
+                    "model \"{}\", {reason}",
+                    model.name()
+                )));
+            }
         }
+
+        Ok(())
+    }
+
+    fn models(&self) -> OpResult<Vec<ModelDefinition>> {
+        decode_models(self.raw)
     }
 
     pub fn new(raw: &'a [u8]) -> Self {
@@ -137,6 +315,7 @@ impl<'a> Modeller<'a> {
             db_pool,
             db_url,
             migrations_dir,
+            migration_name: None,
             bt,
             raw,
         }
@@ -158,14 +337,16 @@ impl<'a> Modeller<'a> {
     }
 
     fn metadata_filename(&self) -> OpResult<String> {
-        self.build_mig_path(&METADATA_FILENAME)
+        self.build_mig_path(METADATA_FILENAME)
     }
 
+    /// the encoded models recorded in the metadata file.
     async fn load_metadata(&self) -> OpResult<Vec<u8>> {
-        let mf = self.migrations_path().join(&METADATA_FILENAME);
+        let mf = self.migrations_path().join(METADATA_FILENAME);
         if mf.exists() {
-            let metadata = tokio::fs::read(&mf).await?;
-            Ok(metadata)
+            let content = tokio::fs::read(&mf).await?;
+            let raw = decode_metadata(&content, &mf.display().to_string())?;
+            Ok(raw.to_vec())
         } else {
             Err(Error::InternalError("missing metadata file. you might need to delete your migrations folder or specify a different migration directory.".to_string()))
         }
@@ -182,8 +363,7 @@ impl<'a> Modeller<'a> {
             .as_array()
             .map(|rows| {
                 rows.iter()
-                    .map(|v| v.as_map().map(|m| m.get(&Value::from("filename")).into()))
-                    .flatten()
+                    .filter_map(|v| v.as_map().map(|m| m.get(&Value::from("filename")).into()))
                     .collect()
             })
             .unwrap_or(vec![]);
@@ -199,30 +379,36 @@ impl<'a> Modeller<'a> {
         let mut paths = Vec::new();
 
         while let Some(entry) = entries.next_entry().await? {
-            paths.push(entry.path());
+            if entry.file_type().await?.is_file()
+                && entry.file_name().to_str().is_some_and(is_migration_file)
+            {
+                paths.push(entry.path());
+            }
         }
 
+        // migrations sort in the order they were generated
+        paths.sort_by(|a, b| {
+            let name = |path: &PathBuf| path.file_name()?.to_str().map(str::to_string);
+            let (a, b) = (name(a).unwrap_or_default(), name(b).unwrap_or_default());
+            migration_order(&a).cmp(&migration_order(&b))
+        });
+
         Ok(paths)
     }
 
     async fn run_pending_migrations(&self) -> OpResult<()> {
         let pvs = self.previous_migrations().await?;
         let mfs = self.migration_files().await?;
-        let metafile = self.metadata_filename()?;
 
         let new_migrations: Vec<&PathBuf> = mfs
             .iter()
             .filter(|file| {
                 if let Some(filename) = file.to_str() {
-                    if filename == &metafile {
-                        return false;
-                    }
-
                     let exists = pvs.iter().find(|pv| pv.as_str() == filename);
                     return exists.is_none();
                 }
 
-                return true;
+                true
             })
             .collect();
 
@@ -233,27 +419,206 @@ impl<'a> Modeller<'a> {
                     Error::InternalError(format!("error parsing migration content {mig:?}: {err}"))
                 })?;
 
-                // run the migration
-                self.db_pool.exec(&sql, vec![]).await?;
-
-                // update migration status
+                // run the migration and update its status
                 let filename = mig.to_str().unwrap_or("");
-                let insert_query = format!("INSERT INTO {MIG_TABLE_NAME} (filename) VALUES(?)");
-                self.db_pool
-                    .exec(&insert_query, vec![filename.into()])
-                    .await?;
+                self.exec_migration(filename, &sql).await?;
             }
         }
 
         Ok(())
     }
 
+    /// executes the statements of a migration one after the other, so
+    /// that drivers which reject multi-statement queries can run it and
+    /// failures point to the exact statement, then records it as applied.
+    /// Postgres and SQLite roll back schema changes, so the statements and
+    /// the record run in one transaction, and a failing migration can be
+    /// fixed and run again. MySQL commits each schema change implicitly.
+    /// Statements that have no effect within a transaction run around it
+    /// on the same connection, see `transaction_stage`.
+    async fn exec_migration(&self, migration: &str, sql: &str) -> OpResult<()> {
+        // statements of a migration depend on each other, so they
+        // must all run on the same connection.
+        let conn = self.db_pool.acquire().await?;
+        let statements = split_statements(sql, &self.bt);
+        let record = format!("INSERT INTO {MIG_TABLE_NAME} (filename) VALUES(?)");
+
+        if self.bt == BackendType::MySql {
+            exec_statements(&conn, migration, statements.iter().enumerate()).await?;
+            conn.exec(&record, vec![migration.into()]).await?;
+            return Ok(());
+        }
+
+        let stage = |stage: Stage| {
+            statements
+                .iter()
+                .enumerate()
+                .filter(move |(_, statement)| transaction_stage(statement) == stage)
+        };
+        exec_statements(&conn, migration, stage(Stage::Before)).await?;
+
+        let tx = conn.begin().await?;
+        let applied = async {
+            exec_statements(&tx, migration, stage(Stage::Within)).await?;
+            tx.exec(&record, vec![migration.into()]).await?;
+            Ok(())
+        }
+        .await;
+
+        let applied = match applied {
+            Ok(()) => tx.commit().await.map_err(Error::from),
+            Err(err) => {
+                tx.rollback().await?;
+                Err(err)
+            }
+        };
+
+        // the connection goes back to the pool, so it is restored
+        // whether or not the migration applied
+        exec_statements(&tx, migration, stage(Stage::After)).await?;
+        applied
+    }
+
     async fn update_metadata(&self) -> OpResult<()> {
         // write metadata
         let mf = self.metadata_filename()?;
         let mut file = open_file(&mf).await?;
-        file.write_all(&self.raw).await?;
+        file.write_all(&encode_metadata(self.raw)).await?;
+
+        Ok(())
+    }
+}
+
+async fn exec_statements(
+    executor: &dyn Executor,
+    migration: &str,
+    statements: impl Iterator<Item = (usize, &String)>,
+) -> OpResult<()> {
+    for (i, statement) in statements {
+        let result = match statement.as_str() {
+            // sqlite reports violations as rows instead of failing
+            "PRAGMA foreign_key_check" => match executor.query(statement, vec![]).await {
+                Ok(rbs::Value::Array(rows)) if !rows.is_empty() => Err(rbatis::Error::from(
+                    format!("foreign key violations: {}", rbs::Value::Array(rows)),
+                )),
+                result => result.map(|_| ()),
+            },
+            _ => executor.exec(statement, vec![]).await.map(|_| ()),
+        };
+
+        if let Err(error) = result {
+            return Err(Error::MigrationError {
+                migration: migration.to_string(),
+                index: i + 1,
+                statement: statement.clone(),
+                error,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// When a statement of a migration runs, relative to its transaction.
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    Before,
+    Within,
+    After,
+}
+
+/// Postgres only uses values added to an enum once they are committed,
+/// and adds them idempotently, see `definitions::diff`. SQLite ignores
+/// `PRAGMA foreign_keys` within transactions, so foreign keys are turned
+/// off before and back on after the transaction of a table rebuild.
+fn transaction_stage(statement: &str) -> Stage {
+    match statement {
+        "PRAGMA foreign_keys=OFF" => Stage::Before,
+        "PRAGMA foreign_keys=ON" => Stage::After,
+        _ if statement.starts_with("ALTER TYPE ") && statement.contains(" ADD VALUE ") => {
+            Stage::Before
+        }
+        _ => Stage::Within,
+    }
+}
+
+fn decode_models(raw: &[u8]) -> OpResult<Vec<ModelDefinition>> {
+    let config = config::standard();
+    match bincode::decode_from_slice(raw, config) {
+        Ok((models, _)) => Ok(models),
+        Err(err) => Err(Error::ParseError(format!(
+            "unable to decode model definitions: {err}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Modeller;
+    use crate::errors::{Error, OpResult};
+    use definitions::backend_type::BackendType;
+    use rbatis::RBatis;
+
+    #[tokio::test]
+    async fn test_exec_migration() -> OpResult<()> {
+        let dir = std::env::temp_dir().join(format!("modeller_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("db.sqlite");
+        let modeller = Modeller {
+            bt: BackendType::Sqlite,
+            db_url: format!("sqlite://{}", path.display()),
+            db_pool: RBatis::new(),
+            migrations_dir: String::new(),
+            migration_name: None,
+            raw: &[],
+        };
+        modeller.connect().await?;
+        modeller.create_migrations_table().await?;
+
+        let sql = "CREATE TABLE a (id INTEGER);\nINSERT INTO missing VALUES (1);";
+        let err = modeller.exec_migration("1_add_a.sql", sql).await;
+        assert!(matches!(err, Err(Error::MigrationError { index: 2, .. })));
+
+        // the table and the record were rolled back with the failing statement
+        let sql = "CREATE TABLE a (id INTEGER);";
+        modeller.exec_migration("1_add_a.sql", sql).await?;
+        assert_eq!(modeller.previous_migrations().await?, vec!["1_add_a.sql"]);
+
+        let sql = "CREATE TABLE parent (id INTEGER PRIMARY KEY, name TEXT);
+            CREATE TABLE child (parent_id INTEGER REFERENCES parent (id) ON DELETE CASCADE);
+            INSERT INTO parent VALUES (1, 'a'), (2, 'b');
+            INSERT INTO child VALUES (1);";
+        modeller.exec_migration("2_add_parent.sql", sql).await?;
+
+        // rebuilding a referenced table neither cascades nor loses rows referenced
+        let rebuild = |filter: &str| {
+            format!(
+                "PRAGMA foreign_keys=OFF;
+                CREATE TABLE _modeller_new_parent (id INTEGER PRIMARY KEY);
+                INSERT INTO _modeller_new_parent (id) SELECT id FROM parent{filter};
+                DROP TABLE parent;
+                ALTER TABLE _modeller_new_parent RENAME TO parent;
+                PRAGMA foreign_key_check;
+                PRAGMA foreign_keys=ON;"
+            )
+        };
+        let err = modeller
+            .exec_migration("3_drop_name.sql", &rebuild(" WHERE id > 1"))
+            .await;
+        assert!(matches!(err, Err(Error::MigrationError { index: 6, .. })));
+        modeller
+            .exec_migration("3_drop_name.sql", &rebuild(""))
+            .await?;
+
+        let count = "SELECT COUNT(*) FROM child JOIN parent ON parent.id = child.parent_id";
+        let children: i64 = modeller.db_pool.query_decode(count, vec![]).await?;
+        assert_eq!(children, 1);
+
+        // foreign keys are enforced again
+        let orphan = "INSERT INTO child VALUES (3)";
+        assert!(modeller.db_pool.exec(orphan, vec![]).await.is_err());
 
+        std::fs::remove_dir_all(dir)?;
         Ok(())
     }
 }
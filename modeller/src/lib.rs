@@ -1,26 +1,107 @@
 use chrono::Utc;
 
 use crate::errors::OpResult;
+use definitions::backend_type::DB_URL_KEY;
+
+// lets the code generated by `parse_models!` refer to
+// `::modeller` from within this crate too.
+extern crate self as modeller;
+
+pub use definitions;
+pub use definitions::{backend_type::BackendType, custom::ModellerType};
+pub use relation::{ForeignKey, Model};
 
 pub mod errors;
+mod history;
 pub mod implementor;
+mod metadata;
+pub mod relation;
+pub mod splitter;
 
-const DB_URL_KEY: &str = "MODELLER_DATABASE_URL";
 const MIG_DIR_KEY: &str = "MODELLER_MIGRATIONS_DIR";
 const DEFAULT_DB: &str = "sqlite://db.sqlite";
 const DEFAULT_MIG_DIR: &str = "migrations";
 const MIG_TABLE_NAME: &str = "mmm_migrations";
 const METADATA_FILENAME: &str = "metadata";
+/// Directory of the migrations directory holding the metadata of the
+/// schema of every migration, named by its hash.
+const SCHEMAS_DIR: &str = "schemas";
+
+/// Filename prefix of migrations generated before versions were
+/// introduced, e.g. `migration_20250101_120000.sql`.
+const LEGACY_MIG_PREFIX: &str = "migration_";
+
+const MAX_SLUG_LEN: usize = 80;
+
+/// Builds a migration filename from a version and a description of
+/// the migration, e.g. `20250101120000123_add_age_to_users.sql`.
+///
+/// The version is the current UTC time down to the millisecond. It is
+/// bumped past `last_version` when needed, so versions stay unique and
+/// increasing even when migrations are generated in the same instant.
+fn generate_migration_filename(name: &str, last_version: Option<u64>) -> String {
+    let now = Utc::now()
+        .format("%Y%m%d%H%M%S%3f")
+        .to_string()
+        .parse::<u64>()
+        .unwrap_or_default();
+
+    let version = match last_version {
+        Some(last) if last >= now => last + 1,
+        _ => now,
+    };
+
+    let slug = slugify(name);
+    if slug.is_empty() {
+        format!("{version}.sql")
+    } else {
+        format!("{version}_{slug}.sql")
+    }
+}
+
+/// Returns the version of a migration from its filename.
+fn migration_version(filename: &str) -> Option<u64> {
+    filename.split(['_', '.']).next()?.parse().ok()
+}
+
+/// Whether `filename` names a migration, either versioned or legacy.
+/// Other files in the migrations directory, e.g. a `README.md` or a
+/// `.gitkeep`, are neither run nor recorded.
+fn is_migration_file(filename: &str) -> bool {
+    filename.ends_with(".sql")
+        && (filename.starts_with(LEGACY_MIG_PREFIX) || migration_version(filename).is_some())
+}
 
-fn generate_migration_filename() -> String {
-    let now = Utc::now().format("%Y%m%d_%H%M%S").to_string();
-    format!("migration_{now}.sql")
+/// Sort key of a migration filename. Legacy migrations predate every
+/// versioned migration and sort by their timestamp, versioned
+/// migrations sort by their version.
+fn migration_order(filename: &str) -> (bool, Option<u64>, &str) {
+    let versioned = !filename.starts_with(LEGACY_MIG_PREFIX);
+    (versioned, migration_version(filename), filename)
+}
+
+/// Converts `name` to a lowercase, underscore separated slug that is
+/// safe to use in a filename.
+fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+
+    slug.truncate(MAX_SLUG_LEN);
+    slug.trim_end_matches('_').to_string()
 }
 
 async fn open_file(path: &str) -> OpResult<tokio::fs::File> {
     let f = tokio::fs::OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(&path)
         .await?;
 
@@ -29,35 +110,15 @@ async fn open_file(path: &str) -> OpResult<tokio::fs::File> {
 
 #[macro_export]
 macro_rules! define_models {
-    (
-        $(
-            $(#[$meta:meta])*
-            $vis:vis struct $name:ident {
-                $(
-                    $(#[$field_attr:meta])*
-                    $field_vis:vis $field:ident : $ty:ty
-                ),* $(,)?
-            }
-        ),*
-    ) => {
+    ($($items:tt)*) => {
         use modeller_parser::parse_models;
-        use crate::implementor::Modeller;
-
-        // parse the input models into a vector of strigified
-        // `ModelDefinition`
-        parse_models! {
-            $(
-                $(#[$meta])*
-                $vis struct $name {
-                    $(
-                        $(#[$field_attr])*
-                        $field_vis $field: $ty,
-                    )*
-                }
-            ),*,
-        }
+        use $crate::implementor::Modeller;
+
+        // parse the input models and enums into a vector of
+        // strigified `ModelDefinition`
+        parse_models! { $($items)* }
 
-        pub fn get_modeller(models: &[u8]) -> Modeller {
+        pub fn get_modeller(models: &[u8]) -> Modeller<'_> {
             Modeller::new(models)
         }
     };
@@ -65,20 +126,71 @@ macro_rules! define_models {
 
 #[cfg(test)]
 mod tests {
-    use crate::errors::OpResult;
+    use crate::{
+        BackendType, ForeignKey, ModellerType, errors::OpResult, is_migration_file, migration_order,
+    };
+
+    #[allow(dead_code)]
+    struct Email(String);
+
+    impl ModellerType for Email {
+        fn sql_type(_bt: &BackendType) -> String {
+            "VARCHAR(320)".to_string()
+        }
+    }
+
+    #[test]
+    fn test_migration_files() {
+        assert!(is_migration_file("20250101120000123_add_age_to_users.sql"));
+        assert!(is_migration_file("20250101120000123.sql"));
+        assert!(is_migration_file("migration_20250102_080000.sql"));
+        assert!(!is_migration_file("metadata"));
+        assert!(!is_migration_file("README.md"));
+        assert!(!is_migration_file(".gitkeep"));
+        assert!(!is_migration_file("notes.sql"));
+        assert!(!is_migration_file(
+            "20250101120000123_add_age_to_users.sql.bak"
+        ));
+    }
+
+    #[test]
+    fn test_migration_order() {
+        let mut filenames = vec![
+            "20250101120000123_add_age_to_users.sql",
+            "migration_20250102_080000.sql",
+            "9999.sql",
+            "migration_20241231_235959.sql",
+        ];
+        filenames.sort_by_key(|filename| migration_order(filename));
+
+        assert_eq!(
+            filenames,
+            vec![
+                "migration_20241231_235959.sql",
+                "migration_20250102_080000.sql",
+                "9999.sql",
+                "20250101120000123_add_age_to_users.sql"
+            ]
+        );
+    }
 
     #[tokio::test]
     async fn test_modeller() -> OpResult<()> {
         define_models! {
+            #[modeller(index(columns(country, user_location)))]
             struct TestModel {
+                #[modeller(signed)]
                 id: u64,
+
+                #[modeller(index)]
                 country: Option<String>,
 
                 #[modeller(name=user_location, default=Lagos, unique)]
-                state: u32,
+                state: String,
 
-                // #[modeller(default=CURRENT_TIMESTAMP)]
-                // created_at: Datetime
+                #[modeller(default=@now)]
+                created_at: chrono::NaiveDateTime,
+                updated_at: Option<chrono::DateTime<chrono::Utc>>
             },
             #[table_name = "custom_table_name"]
             pub struct AnotherModel {
@@ -88,15 +200,41 @@ mod tests {
                 #[modeller(unique, length=12)]
                 username: String,
 
-                #[modeller(default=18)]
+                #[modeller(default=18, check = "age >= 13")]
                 age: Option<u32>,
 
                 #[modeller(type=NULLABLE TEXT)]
-                bio: String
+                bio: String,
+
+                verified: bool,
+                rating: Option<f64>,
+                role: Role,
+
+                #[modeller(via = Email)]
+                email: Option<Email>,
+
+                #[modeller(enum(draft, published))]
+                status: Option<String>
+            },
+            #[modeller(many_to_many = AnotherModel, through = post_readers)]
+            struct Post {
+                #[modeller(serial)]
+                id: u64,
+
+                #[modeller(on_delete = cascade)]
+                author: ForeignKey<AnotherModel>,
+
+                #[modeller(belongs_to = AnotherModel, on_delete = set_null)]
+                editor_id: Option<i32>
+            },
+            #[allow(dead_code)]
+            enum Role {
+                Admin,
+                Member
             }
         }
 
-        let streams = modeller_definition_streams();
+        let streams = modeller_definition_streams()?;
         let modeller = get_modeller(&streams);
 
         modeller.run().await?;
@@ -0,0 +1,85 @@
+use crate::errors::Error;
+
+/// Version of the encoding of the models recorded in the metadata
+/// file. Bump it whenever the layout of `ModelDefinition`, or of
+/// anything it contains, changes.
+pub(crate) const METADATA_VERSION: u32 = 1;
+
+const VERSION_KEY: &str = "modeller:metadata v";
+
+/// Content of the metadata file recording the encoded models `raw`,
+/// behind a header with the version of their encoding.
+pub(crate) fn encode_metadata(raw: &[u8]) -> Vec<u8> {
+    let mut content = format!("{VERSION_KEY}{METADATA_VERSION}\n").into_bytes();
+    content.extend_from_slice(raw);
+    content
+}
+
+/// Returns the encoded models recorded in the metadata file `path`.
+/// Models encoded by another version of modeller cannot be decoded
+/// reliably, so files without the current version are rejected
+/// instead of being diffed against. Files created empty record no
+/// models yet.
+pub(crate) fn decode_metadata<'a>(content: &'a [u8], path: &str) -> Result<&'a [u8], Error> {
+    if content.is_empty() {
+        return Ok(content);
+    }
+
+    let header = content
+        .strip_prefix(VERSION_KEY.as_bytes())
+        .and_then(|rest| {
+            let end = rest.iter().position(|byte| *byte == b'\n')?;
+            let version = std::str::from_utf8(&rest[..end]).ok()?.parse().ok()?;
+            Some((version, &rest[end + 1..]))
+        });
+
+    match header {
+        Some((METADATA_VERSION, raw)) => Ok(raw),
+        header => Err(Error::OutdatedMetadata {
+            path: path.to_string(),
+            version: header.map(|(version, _)| version),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{METADATA_VERSION, decode_metadata, encode_metadata};
+    use crate::errors::Error;
+    use definitions::bincode::{self, config};
+
+    #[test]
+    fn test_decode_metadata() {
+        let raw = b"encoded models";
+        let content = encode_metadata(raw);
+        assert_eq!(decode_metadata(&content, "metadata").unwrap(), raw);
+        assert!(decode_metadata(b"", "metadata").unwrap().is_empty());
+
+        // models written before metadata was versioned: the table name, then the
+        // name, type, serial, unique, default and length of each field
+        let field = (
+            "id".to_string(),
+            0u32,
+            true,
+            false,
+            None::<String>,
+            None::<usize>,
+        );
+        let legacy =
+            bincode::encode_to_vec(vec![("user".to_string(), vec![field])], config::standard())
+                .unwrap();
+        let err = decode_metadata(&legacy, "migrations/metadata").unwrap_err();
+        assert!(matches!(err, Error::OutdatedMetadata { version: None, .. }));
+        assert!(err.to_string().contains("delete migrations/metadata"));
+
+        let newer = format!("modeller:metadata v{}\n", METADATA_VERSION + 1);
+        let err = decode_metadata(newer.as_bytes(), "metadata").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::OutdatedMetadata {
+                version: Some(_),
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+
+/// A model declared in `define_models!` whose primary key is a
+/// single column. `parse_models!` implements it for such models.
+pub trait Model {
+    /// Rust type of the primary key field.
+    type PrimaryKey;
+}
+
+/// A reference to a row of `M` by its primary key. A field
+/// `author: ForeignKey<User>` is stored in an `author_id` column
+/// with the type of the primary key of `User` and a foreign key to it.
+pub struct ForeignKey<M: Model> {
+    pub id: M::PrimaryKey,
+}
+
+impl<M: Model> ForeignKey<M> {
+    pub fn new(id: M::PrimaryKey) -> Self {
+        ForeignKey { id }
+    }
+}
+
+impl<M: Model> fmt::Debug for ForeignKey<M>
+where
+    M::PrimaryKey: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForeignKey").field("id", &self.id).finish()
+    }
+}
+
+impl<M: Model> Clone for ForeignKey<M>
+where
+    M::PrimaryKey: Clone,
+{
+    fn clone(&self) -> Self {
+        ForeignKey {
+            id: self.id.clone(),
+        }
+    }
+}
+
+impl<M: Model> PartialEq for ForeignKey<M>
+where
+    M::PrimaryKey: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
@@ -0,0 +1,244 @@
+use definitions::backend_type::BackendType;
+
+const DEFAULT_DELIMITER: &str = ";";
+
+/// Splits the content of a migration file into the individual
+/// statements it contains so they can be executed one after the other.
+///
+/// The splitter understands:
+/// - single and double quoted strings (with `''` escapes, and backslash
+///   escapes on MySQL and in Postgres `E'...'` strings), as well as
+///   backtick quoted identifiers.
+/// - `-- ...` line comments (and `# ...` on MySQL), which are dropped,
+///   and `/* ... */` block comments, which are kept.
+/// - dollar-quoted bodies (`$$ ... $$`, `$tag$ ... $tag$`) on Postgres.
+/// - `DELIMITER <token>` lines which change the statement delimiter
+///   until the next `DELIMITER` line, e.g. for MySQL routines and SQLite
+///   triggers. These lines are never sent to the database.
+///
+/// Empty statements are skipped.
+pub fn split_statements(sql: &str, bt: &BackendType) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut delimiter = DEFAULT_DELIMITER.to_string();
+    let mut rest = sql;
+
+    while !rest.is_empty() {
+        if current.trim().is_empty()
+            && let Some((directive, remaining)) = parse_delimiter_directive(rest)
+        {
+            delimiter = directive.to_string();
+            rest = remaining;
+            continue;
+        }
+
+        if rest.starts_with(delimiter.as_str()) {
+            push_statement(&mut statements, &mut current);
+            rest = &rest[delimiter.len()..];
+            continue;
+        }
+
+        // `E'...'` only starts an escape string outside of a word
+        let after_word = current
+            .chars()
+            .last()
+            .is_some_and(|c| c == '_' || c.is_alphanumeric());
+        let len = token_len(rest, after_word, bt);
+        let (token, remaining) = rest.split_at(len);
+
+        if !is_line_comment(token, bt) {
+            current.push_str(token);
+        } else if token.ends_with('\n') {
+            current.push('\n');
+        }
+
+        rest = remaining;
+    }
+
+    push_statement(&mut statements, &mut current);
+    statements
+}
+
+fn push_statement(statements: &mut Vec<String>, current: &mut String) {
+    let statement = current.trim();
+    if !statement.is_empty() {
+        statements.push(statement.to_string());
+    }
+
+    current.clear();
+}
+
+/// Checks if `sql` starts with a `DELIMITER <token>` line and returns
+/// the new delimiter and the remaining sql after the directive.
+fn parse_delimiter_directive(sql: &str) -> Option<(&str, &str)> {
+    let trimmed = sql.trim_start();
+    let keyword = trimmed.get(..9)?;
+
+    if !keyword.eq_ignore_ascii_case("DELIMITER") {
+        return None;
+    }
+
+    let after = &trimmed[9..];
+    if !after.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    let (line, remaining) = after.split_once('\n').unwrap_or((after, ""));
+    let directive = line.trim();
+
+    if directive.is_empty() {
+        return None;
+    }
+
+    Some((directive, remaining))
+}
+
+fn is_line_comment(token: &str, bt: &BackendType) -> bool {
+    token.starts_with("--") || (matches!(bt, BackendType::MySql) && token.starts_with('#'))
+}
+
+/// Returns the byte length of the token at the start of `sql`. A token
+/// is either a quoted section, a comment or a single character.
+/// `after_word` tells whether `sql` follows a word character.
+fn token_len(sql: &str, after_word: bool, bt: &BackendType) -> usize {
+    use BackendType::*;
+
+    let backslash_escapes = matches!(bt, MySql);
+
+    if sql.starts_with("--") || (matches!(bt, MySql) && sql.starts_with('#')) {
+        return sql.find('\n').map(|i| i + 1).unwrap_or(sql.len());
+    }
+
+    if let Some(body) = sql.strip_prefix("/*") {
+        return body.find("*/").map(|i| i + 4).unwrap_or(sql.len());
+    }
+
+    if matches!(bt, Postgres)
+        && let Some(tag) = dollar_quote_tag(sql)
+    {
+        let body = &sql[tag.len()..];
+        return body
+            .find(tag)
+            .map(|i| tag.len() * 2 + i)
+            .unwrap_or(sql.len());
+    }
+
+    // postgres escape strings, e.g. `E'it\'s'`
+    if matches!(bt, Postgres)
+        && !after_word
+        && let Some(string) = sql.strip_prefix(['E', 'e'])
+        && string.starts_with('\'')
+    {
+        return 1 + quoted_len(string, '\'', true);
+    }
+
+    match sql.chars().next() {
+        Some(quote @ ('\'' | '"')) => quoted_len(sql, quote, backslash_escapes),
+        Some('`') if !matches!(bt, Postgres) => quoted_len(sql, '`', false),
+        Some(c) => c.len_utf8(),
+        None => 0,
+    }
+}
+
+/// Length of a section quoted with `quote`, including both quotes.
+/// A doubled quote inside the section is treated as an escaped quote.
+fn quoted_len(sql: &str, quote: char, backslash_escapes: bool) -> usize {
+    let mut chars = sql.char_indices().skip(1).peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if backslash_escapes && c == '\\' {
+            chars.next();
+        } else if c == quote {
+            match chars.peek() {
+                Some((_, next)) if *next == quote => {
+                    chars.next();
+                }
+                _ => return i + c.len_utf8(),
+            }
+        }
+    }
+
+    sql.len()
+}
+
+/// Returns the opening tag (e.g. `$$` or `$body$`) if `sql` starts
+/// with a Postgres dollar quote.
+fn dollar_quote_tag(sql: &str) -> Option<&str> {
+    let body = sql.strip_prefix('$')?;
+    let end = body.find('$')?;
+    let tag = &body[..end];
+
+    let valid = tag
+        .chars()
+        .enumerate()
+        .all(|(i, c)| c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()));
+
+    valid.then(|| &sql[..end + 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_statements;
+    use definitions::backend_type::BackendType;
+
+    #[test]
+    fn test_split_statements() {
+        let sql = "
+            -- create the table; with a comment
+            CREATE TABLE a (name VARCHAR DEFAULT 'x;y', note TEXT DEFAULT 'it''s');
+            /* block; comment */ INSERT INTO a (name) VALUES (\"a;b\");;
+        ";
+
+        let statements = split_statements(sql, &BackendType::Sqlite);
+        assert_eq!(
+            statements,
+            vec![
+                "CREATE TABLE a (name VARCHAR DEFAULT 'x;y', note TEXT DEFAULT 'it''s')",
+                "/* block; comment */ INSERT INTO a (name) VALUES (\"a;b\")",
+            ]
+        );
+
+        let sql = "
+            CREATE FUNCTION f() RETURNS trigger AS $body$
+            BEGIN
+                NEW.updated_at = now(); RETURN NEW;
+            END;
+            $body$ LANGUAGE plpgsql;
+            DO $$ BEGIN PERFORM 1; END $$;
+        ";
+
+        let statements = split_statements(sql, &BackendType::Postgres);
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].ends_with("$body$ LANGUAGE plpgsql"));
+
+        let sql = "INSERT INTO a (name) VALUES (E'it\\'s; x'), (e'\\\\'); SELECT 1 WHERE 'x\\' LIKE'x\\'; SELECT 2;";
+        let statements = split_statements(sql, &BackendType::Postgres);
+        assert_eq!(
+            statements,
+            vec![
+                "INSERT INTO a (name) VALUES (E'it\\'s; x'), (e'\\\\')",
+                "SELECT 1 WHERE 'x\\' LIKE'x\\'",
+                "SELECT 2",
+            ]
+        );
+
+        let sql = "
+            INSERT INTO a (name) VALUES ('it\\'s; fine');
+            DELIMITER //
+            CREATE PROCEDURE p() BEGIN SELECT 1; SELECT 2; END //
+            DELIMITER ;
+            # trailing comment
+            SELECT 3;
+        ";
+
+        let statements = split_statements(sql, &BackendType::MySql);
+        assert_eq!(
+            statements,
+            vec![
+                "INSERT INTO a (name) VALUES ('it\\'s; fine')",
+                "CREATE PROCEDURE p() BEGIN SELECT 1; SELECT 2; END",
+                "SELECT 3",
+            ]
+        );
+    }
+}
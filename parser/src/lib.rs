@@ -9,13 +9,24 @@ use definitions::core::DefinitionStream;
 fn impl_parse_models(stream: TokenStream) -> TokenStream {
     let def_stream = parse_macro_input!(stream as DefinitionStream);
     let items = def_stream.items();
+    let enums = def_stream.enums();
+    let aliases = def_stream.aliases();
+    let definitions_fn = match def_stream.definitions_fn() {
+        Ok(definitions_fn) => definitions_fn,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let model_impls = match def_stream.model_impls() {
+        Ok(model_impls) => model_impls,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
-    let original_structs = items.into_iter().map(|item| {
+    let original_structs = items.iter().map(|item| {
         let vis = &item.vis;
         let attrs = &item.attrs;
         let attrs: Vec<&Attribute> = attrs
-            .into_iter()
+            .iter()
             .filter(|attr| should_keep_attr(attr, "table_name"))
+            .filter(|attr| should_keep_attr(attr, "modeller"))
             .collect();
 
         let ident = &item.ident;
@@ -41,7 +52,10 @@ fn impl_parse_models(stream: TokenStream) -> TokenStream {
     quote! {
 
         #(#original_structs)*
-        #def_stream
+        #(#enums)*
+        #(#aliases)*
+        #model_impls
+        #definitions_fn
 
     }
     .into()
@@ -66,11 +80,9 @@ fn should_keep_attr(attr: &Attribute, ident_key: &'static str) -> bool {
 }
 
 fn strip_field_attrs(mut field: Field) -> Field {
-    field.attrs = field
+    field
         .attrs
-        .into_iter()
-        .filter(|attr| should_keep_attr(attr, "modeller"))
-        .collect();
+        .retain(|attr| should_keep_attr(attr, "modeller"));
     field
 }
 